@@ -1,11 +1,14 @@
-use crate::token::Token;
+use crate::integer::Integer;
+use crate::token::{Span, Token};
 
-trait Node {
+pub trait Node {
     fn token_literal(&self) -> String;
     fn string(&self) -> String;
+    fn span(&self) -> Span;
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Eq, PartialEq, Debug, Clone)]
 pub enum Statement<'a> {
     LetStatement(LetStatement<'a>),
     ReturnStatement(ReturnStatement<'a>),
@@ -15,71 +18,166 @@ pub enum Statement<'a> {
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub enum Expression<'a> {
     Identifier(Identifier<'a>),
+    IntegerLiteral(IntegerLiteral<'a>),
+    FloatLiteral(FloatLiteral<'a>),
+    StringLiteral(StringLiteral<'a>),
+    Boolean(Boolean<'a>),
+    PrefixExpression(PrefixExpression<'a>),
+    InfixExpression(InfixExpression<'a>),
+    IfExpression(IfExpression<'a>),
+    FunctionLiteral(FunctionLiteral<'a>),
+    CallExpression(CallExpression<'a>),
+    ArrayLiteral(ArrayLiteral<'a>),
+    IndexExpression(IndexExpression<'a>),
+    HashLiteral(HashLiteral<'a>),
 }
 
-//ToDo: remove the Options after handling the expressions
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Debug, Clone)]
 pub struct LetStatement<'a> {
     pub token: Token<'a>,
-    pub name: Option<Identifier<'a>>,
-    pub value: Option<Expression<'a>>,
+    pub name: Identifier<'a>,
+    pub value: Expression<'a>,
+    pub span: Span,
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Debug, Clone)]
 pub struct ReturnStatement<'a> {
     pub token: Token<'a>,
-    pub return_value: Option<Expression<'a>>,
+    pub return_value: Expression<'a>,
+    pub span: Span,
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Debug, Clone)]
 pub struct ExpressionStatement<'a> {
     pub token: Token<'a>,
     pub expression: Expression<'a>,
+    pub span: Span,
 }
 
 impl Node for Statement<'_> {
     fn token_literal(&self) -> String {
         match self {
-            Statement::ReturnStatement(x) => x.token.literal.clone(),
-            Statement::LetStatement(x) => x.token.literal.clone(),
-            Statement::ExpressionStatement(x) => x.token.literal.clone(),
+            Statement::ReturnStatement(x) => x.token.lexeme.to_string(),
+            Statement::LetStatement(x) => x.token.lexeme.to_string(),
+            Statement::ExpressionStatement(x) => x.token.lexeme.to_string(),
         }
     }
     fn string(&self) -> String {
         match self {
             Statement::ReturnStatement(x) => {
                 let mut out = String::new();
-                &out.push_str(&self.token_literal());
-                &out.push_str(" ");
-
-                if x.return_value.is_some() {
-                    let expression = x.return_value.as_ref().unwrap();
-                    match expression {
-                        Expression::Identifier(x) => out.push_str(&x.value),
-                    }
-                }
-                &out.push_str(";");
+                out.push_str(&self.token_literal());
+                out.push(' ');
+                out.push_str(&x.return_value.string());
+                out.push(';');
                 out
             }
             Statement::LetStatement(x) => {
                 let mut out = String::new();
-                &out.push_str(&self.token_literal());
-                &out.push_str(" ");
-                &out.push_str(&x.name.as_ref().unwrap().value);
-                &out.push_str(" = ");
-
-                if x.value.is_some() {
-                    let expression = x.value.as_ref().unwrap();
-                    match expression {
-                        Expression::Identifier(x) => out.push_str(&x.value),
-                    }
+                out.push_str(&self.token_literal());
+                out.push(' ');
+                out.push_str(&x.name.value);
+                out.push_str(" = ");
+                out.push_str(&x.value.string());
+                out.push(';');
+                out
+            }
+            Statement::ExpressionStatement(x) => x.expression.string(),
+        }
+    }
+    fn span(&self) -> Span {
+        match self {
+            Statement::ReturnStatement(x) => x.span,
+            Statement::LetStatement(x) => x.span,
+            Statement::ExpressionStatement(x) => x.span,
+        }
+    }
+}
+
+impl Node for Expression<'_> {
+    fn token_literal(&self) -> String {
+        match self {
+            Expression::Identifier(x) => x.token_literal(),
+            Expression::IntegerLiteral(x) => x.token.lexeme.to_string(),
+            Expression::FloatLiteral(x) => x.token.lexeme.to_string(),
+            Expression::StringLiteral(x) => x.token.lexeme.to_string(),
+            Expression::Boolean(x) => x.token.lexeme.to_string(),
+            Expression::PrefixExpression(x) => x.token.lexeme.to_string(),
+            Expression::InfixExpression(x) => x.token.lexeme.to_string(),
+            Expression::IfExpression(x) => x.token.lexeme.to_string(),
+            Expression::FunctionLiteral(x) => x.token.lexeme.to_string(),
+            Expression::CallExpression(x) => x.token.lexeme.to_string(),
+            Expression::ArrayLiteral(x) => x.token.lexeme.to_string(),
+            Expression::IndexExpression(x) => x.token.lexeme.to_string(),
+            Expression::HashLiteral(x) => x.token.lexeme.to_string(),
+        }
+    }
+    fn string(&self) -> String {
+        match self {
+            Expression::Identifier(x) => x.string(),
+            Expression::IntegerLiteral(x) => x.token.lexeme.to_string(),
+            Expression::FloatLiteral(x) => x.token.lexeme.to_string(),
+            Expression::StringLiteral(x) => quote(&x.value),
+            Expression::Boolean(x) => x.token.lexeme.to_string(),
+            Expression::PrefixExpression(x) => {
+                format!("({}{})", x.operator, x.right.string())
+            }
+            Expression::InfixExpression(x) => {
+                format!("({} {} {})", x.left.string(), x.operator, x.right.string())
+            }
+            Expression::IfExpression(x) => {
+                let mut out = format!("if{} {}", x.condition.string(), x.consequence.string());
+                if let Some(alternative) = &x.alternative {
+                    out.push_str("else ");
+                    out.push_str(&alternative.string());
                 }
-                &out.push_str(";");
                 out
             }
-            Statement::ExpressionStatement(x) => match &x.expression {
-                Expression::Identifier(x) => x.value.clone(),
-            },
+            Expression::FunctionLiteral(x) => {
+                let params: Vec<String> = x.parameters.iter().map(|p| p.string()).collect();
+                format!(
+                    "{}({}) {}",
+                    x.token.lexeme,
+                    params.join(", "),
+                    x.body.string()
+                )
+            }
+            Expression::CallExpression(x) => {
+                let args: Vec<String> = x.arguments.iter().map(|a| a.string()).collect();
+                format!("{}({})", x.function.string(), args.join(", "))
+            }
+            Expression::ArrayLiteral(x) => {
+                let elements: Vec<String> = x.elements.iter().map(|e| e.string()).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            Expression::IndexExpression(x) => {
+                format!("({}[{}])", x.left.string(), x.index.string())
+            }
+            Expression::HashLiteral(x) => {
+                let pairs: Vec<String> = x
+                    .pairs
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k.string(), v.string()))
+                    .collect();
+                format!("{{{}}}", pairs.join(", "))
+            }
+        }
+    }
+    fn span(&self) -> Span {
+        match self {
+            Expression::Identifier(x) => x.span(),
+            Expression::IntegerLiteral(x) => x.token.span,
+            Expression::FloatLiteral(x) => x.token.span,
+            Expression::StringLiteral(x) => x.token.span,
+            Expression::Boolean(x) => x.token.span,
+            Expression::PrefixExpression(x) => x.span,
+            Expression::InfixExpression(x) => x.span,
+            Expression::IfExpression(x) => x.span,
+            Expression::FunctionLiteral(x) => x.span,
+            Expression::CallExpression(x) => x.span,
+            Expression::ArrayLiteral(x) => x.span,
+            Expression::IndexExpression(x) => x.span,
+            Expression::HashLiteral(x) => x.span,
         }
     }
 }
@@ -92,11 +190,158 @@ pub struct Identifier<'a> {
 
 impl Node for Identifier<'_> {
     fn token_literal(&self) -> String {
-        self.token.literal.clone()
+        self.token.lexeme.to_string()
     }
     fn string(&self) -> String {
         self.value.clone()
     }
+    fn span(&self) -> Span {
+        self.token.span
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct IntegerLiteral<'a> {
+    pub token: Token<'a>,
+    pub value: Integer,
+}
+
+#[derive(Debug, Clone)]
+pub struct FloatLiteral<'a> {
+    pub token: Token<'a>,
+    pub value: f64,
+}
+
+// Literals are equal when they are written the same, which also makes f64 usable in an Eq type
+impl PartialEq for FloatLiteral<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.token == other.token
+    }
+}
+
+impl Eq for FloatLiteral<'_> {}
+
+// The value has the escape sequences of the source already replaced
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct StringLiteral<'a> {
+    pub token: Token<'a>,
+    pub value: String,
+}
+
+// Write the string back the way it can be read by the lexer, in double quotes and with the
+// special characters escaped
+fn quote(value: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            ch if ch.is_control() => out.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Boolean<'a> {
+    pub token: Token<'a>,
+    pub value: bool,
+}
+
+// Operator applied in front of a single operand, e.g. -5 or !ok
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct PrefixExpression<'a> {
+    pub token: Token<'a>, // The prefix token, e.g. ! or -
+    pub operator: String,
+    pub right: Box<Expression<'a>>,
+    pub span: Span,
+}
+
+// Binary operator between two operands, e.g. 5 + 5 or a == b
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct InfixExpression<'a> {
+    pub token: Token<'a>, // The operator token, e.g. +
+    pub left: Box<Expression<'a>>,
+    pub operator: String,
+    pub right: Box<Expression<'a>>,
+    pub span: Span,
+}
+
+// Statements enclosed in braces, e.g. the body of a function
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct BlockStatement<'a> {
+    pub token: Token<'a>, // The { token
+    pub statements: Vec<Statement<'a>>,
+    pub span: Span,
+}
+
+impl Node for BlockStatement<'_> {
+    fn token_literal(&self) -> String {
+        self.token.lexeme.to_string()
+    }
+    fn string(&self) -> String {
+        let mut out = String::new();
+        for s in &self.statements {
+            out.push_str(&s.string());
+        }
+        out
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct IfExpression<'a> {
+    pub token: Token<'a>, // The 'if' token
+    pub condition: Box<Expression<'a>>,
+    pub consequence: BlockStatement<'a>,
+    pub alternative: Option<BlockStatement<'a>>,
+    pub span: Span,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct FunctionLiteral<'a> {
+    pub token: Token<'a>, // The 'fn' token
+    pub parameters: Vec<Identifier<'a>>,
+    pub body: BlockStatement<'a>,
+    pub span: Span,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct CallExpression<'a> {
+    pub token: Token<'a>,              // The ( token
+    pub function: Box<Expression<'a>>, // Identifier or FunctionLiteral
+    pub arguments: Vec<Expression<'a>>,
+    pub span: Span,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ArrayLiteral<'a> {
+    pub token: Token<'a>, // The [ token
+    pub elements: Vec<Expression<'a>>,
+    pub span: Span,
+}
+
+// Element access, e.g. myArray[1]
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct IndexExpression<'a> {
+    pub token: Token<'a>, // The [ token
+    pub left: Box<Expression<'a>>,
+    pub index: Box<Expression<'a>>,
+    pub span: Span,
+}
+
+// The pairs are kept in source order, which is also the order they are evaluated in
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct HashLiteral<'a> {
+    pub token: Token<'a>, // The { token
+    pub pairs: Vec<(Expression<'a>, Expression<'a>)>,
+    pub span: Span,
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -106,19 +351,25 @@ pub struct Program<'a> {
 
 impl Node for Program<'_> {
     fn token_literal(&self) -> String {
-        return if self.statements.len() > 0 {
+        if !self.statements.is_empty() {
             self.statements[0].token_literal()
         } else {
             String::from("")
-        };
+        }
     }
     fn string(&self) -> String {
         let mut out = String::new();
         for s in &self.statements {
-            &out.push_str(&s.string());
+            out.push_str(&s.string());
         }
         out
     }
+    fn span(&self) -> Span {
+        match (self.statements.first(), self.statements.last()) {
+            (Some(first), Some(last)) => first.span().to(&last.span()),
+            _ => Span::default(),
+        }
+    }
 }
 
 impl<'a> Program<'a> {
@@ -137,23 +388,27 @@ mod tests {
         let program = Program {
             statements: vec![Statement::LetStatement(LetStatement {
                 token: Token {
-                    ttype: LET,
-                    literal: String::from("let"),
+                    kind: LET,
+                    lexeme: "let",
+                    span: Span::default(),
                 },
-                name: Some(Identifier {
+                name: Identifier {
                     token: Token {
-                        ttype: IDENT,
-                        literal: String::from("myVar"),
+                        kind: IDENT,
+                        lexeme: "myVar",
+                        span: Span::default(),
                     },
                     value: String::from("myVar"),
-                }),
-                value: Some(Expression::Identifier(Identifier {
+                },
+                value: Expression::Identifier(Identifier {
                     token: Token {
-                        ttype: IDENT,
-                        literal: String::from("anotherVar"),
+                        kind: IDENT,
+                        lexeme: "anotherVar",
+                        span: Span::default(),
                     },
                     value: String::from("anotherVar"),
-                })),
+                }),
+                span: Span::default(),
             })],
         };
 
@@ -1,7 +1,16 @@
 use crate::repl::start;
+use crate::runner;
+use std::env;
 use std::io::{self, Write};
+use std::process;
+
+// Run the script given on the command line, or start the REPL when there is none
+pub fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        process::exit(runner::run(&args));
+    }
 
-fn main() {
     io::stdout()
         .write_all(b"Hello! This is the Monkey programming language!\n")
         .unwrap();
@@ -0,0 +1,3 @@
+fn main() {
+    monkey_interpreter::main()
+}
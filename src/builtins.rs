@@ -0,0 +1,228 @@
+use crate::integer::{self, Integer};
+use crate::object::*;
+use std::rc::Rc;
+
+// Native functions available in every Monkey program. Identifiers are only looked up here
+// when no binding with the same name exists, so programs can shadow them.
+pub const BUILTINS: [Builtin; 6] = [
+    Builtin {
+        name: "len",
+        func: len,
+    },
+    Builtin {
+        name: "first",
+        func: first,
+    },
+    Builtin {
+        name: "last",
+        func: last,
+    },
+    Builtin {
+        name: "rest",
+        func: rest,
+    },
+    Builtin {
+        name: "push",
+        func: push,
+    },
+    Builtin {
+        name: "puts",
+        func: puts,
+    },
+];
+
+// Position of the builtin in BUILTINS, the VM falls back to it for a global without a value
+pub fn lookup(name: &str) -> Option<usize> {
+    BUILTINS.iter().position(|b| b.name == name)
+}
+
+fn check_arity<'a>(args: &[Object<'a>], want: usize) -> Option<Object<'a>> {
+    if args.len() != want {
+        return Some(Object::Error(format!(
+            "wrong number of arguments: want={}, got={}",
+            want,
+            args.len()
+        )));
+    }
+    None
+}
+
+fn not_an_array<'a>(name: &str, arg: &Object) -> Object<'a> {
+    Object::Error(format!(
+        "argument to `{}` must be ARRAY, got {}",
+        name,
+        arg.object_type()
+    ))
+}
+
+// Number of characters of a string, elements of an array or pairs of a hash
+fn len<'a>(args: &[Object<'a>]) -> Object<'a> {
+    if let Some(err) = check_arity(args, 1) {
+        return err;
+    }
+
+    match &args[0] {
+        Object::String(x) => Object::Integer(length(x.chars().count())),
+        Object::Array(x) => Object::Integer(length(x.len())),
+        Object::Hash(x) => Object::Integer(length(x.len())),
+        arg => Object::Error(format!(
+            "argument to `len` not supported, got {}",
+            arg.object_type()
+        )),
+    }
+}
+
+fn length(len: usize) -> Integer {
+    integer::from_i64(len as i64)
+}
+
+fn first<'a>(args: &[Object<'a>]) -> Object<'a> {
+    if let Some(err) = check_arity(args, 1) {
+        return err;
+    }
+
+    match &args[0] {
+        Object::Array(x) => x.first().cloned().unwrap_or(Object::Null),
+        arg => not_an_array("first", arg),
+    }
+}
+
+fn last<'a>(args: &[Object<'a>]) -> Object<'a> {
+    if let Some(err) = check_arity(args, 1) {
+        return err;
+    }
+
+    match &args[0] {
+        Object::Array(x) => x.last().cloned().unwrap_or(Object::Null),
+        arg => not_an_array("last", arg),
+    }
+}
+
+// New array with everything but the first element, null for an empty array
+fn rest<'a>(args: &[Object<'a>]) -> Object<'a> {
+    if let Some(err) = check_arity(args, 1) {
+        return err;
+    }
+
+    match &args[0] {
+        Object::Array(x) if x.is_empty() => Object::Null,
+        Object::Array(x) => Object::Array(Rc::new(x[1..].to_vec())),
+        arg => not_an_array("rest", arg),
+    }
+}
+
+// New array with the element added at the end, the argument is left as it is
+fn push<'a>(args: &[Object<'a>]) -> Object<'a> {
+    if let Some(err) = check_arity(args, 2) {
+        return err;
+    }
+
+    match &args[0] {
+        Object::Array(x) => {
+            let mut elements = x.to_vec();
+            elements.push(args[1].clone());
+            Object::Array(Rc::new(elements))
+        }
+        arg => not_an_array("push", arg),
+    }
+}
+
+// Print every argument on its own line
+fn puts<'a>(args: &[Object<'a>]) -> Object<'a> {
+    for arg in args {
+        println!("{}", arg.inspect());
+    }
+    Object::Null
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int<'a>(value: i64) -> Object<'a> {
+        Object::Integer(integer::from_i64(value))
+    }
+
+    fn array<'a>(elements: Vec<i64>) -> Object<'a> {
+        Object::Array(Rc::new(elements.into_iter().map(int).collect()))
+    }
+
+    #[test]
+    fn builtin_functions() {
+        let tests = vec![
+            ("len", vec![Object::String(String::from(""))], "0"),
+            ("len", vec![Object::String(String::from("four"))], "4"),
+            ("len", vec![Object::String(String::from("été"))], "3"),
+            ("len", vec![array(vec![1, 2, 3])], "3"),
+            ("first", vec![array(vec![1, 2, 3])], "1"),
+            ("first", vec![array(vec![])], "null"),
+            ("last", vec![array(vec![1, 2, 3])], "3"),
+            ("last", vec![array(vec![])], "null"),
+            ("rest", vec![array(vec![1, 2, 3])], "[2, 3]"),
+            ("rest", vec![array(vec![1])], "[]"),
+            ("rest", vec![array(vec![])], "null"),
+            ("push", vec![array(vec![]), int(1)], "[1]"),
+            (
+                "push",
+                vec![array(vec![1]), Object::Boolean(true)],
+                "[1, true]",
+            ),
+            ("puts", vec![], "null"),
+            (
+                "len",
+                vec![int(1)],
+                "ERROR: argument to `len` not supported, got INTEGER",
+            ),
+            (
+                "len",
+                vec![int(1), int(2)],
+                "ERROR: wrong number of arguments: want=1, got=2",
+            ),
+            (
+                "first",
+                vec![int(1)],
+                "ERROR: argument to `first` must be ARRAY, got INTEGER",
+            ),
+            (
+                "last",
+                vec![Object::Null],
+                "ERROR: argument to `last` must be ARRAY, got NULL",
+            ),
+            (
+                "rest",
+                vec![Object::String(String::from("abc"))],
+                "ERROR: argument to `rest` must be ARRAY, got STRING",
+            ),
+            (
+                "push",
+                vec![int(1), int(1)],
+                "ERROR: argument to `push` must be ARRAY, got INTEGER",
+            ),
+            (
+                "push",
+                vec![array(vec![])],
+                "ERROR: wrong number of arguments: want=2, got=1",
+            ),
+        ];
+
+        for (name, args, expected) in tests {
+            let builtin = BUILTINS[lookup(name).unwrap()];
+            let result = (builtin.func)(&args);
+            assert_eq!(
+                result.inspect(),
+                expected,
+                "wrong result for {}({:?})",
+                name,
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn push_leaves_argument_unchanged() {
+        let original = array(vec![1]);
+        push(&[original.clone(), int(2)]);
+
+        assert_eq!(original.inspect(), "[1]");
+    }
+}
@@ -0,0 +1,274 @@
+use std::convert::TryInto;
+
+pub type Instructions = Vec<u8>;
+
+#[allow(clippy::enum_variant_names)]
+#[repr(u8)]
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Opcode {
+    OpConstant,
+    OpPop,
+    OpAdd,
+    OpSub,
+    OpMul,
+    OpDiv,
+    OpTrue,
+    OpFalse,
+    OpNull,
+    OpEqual,
+    OpNotEqual,
+    OpGreaterThan,
+    OpLessThan,
+    OpMinus,
+    OpBang,
+    OpJumpNotTruthy,
+    OpJump,
+    OpGetGlobal,
+    OpSetGlobal,
+    OpGetLocal,
+    OpSetLocal,
+    OpGetFree,
+    OpCall,
+    OpReturnValue,
+    OpReturn,
+    OpClosure,
+    OpCurrentClosure,
+    OpArray,
+    OpIndex,
+    OpHash,
+}
+
+// Name and operand widths (in bytes) of an opcode
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+const OPCODES: [Opcode; 30] = [
+    Opcode::OpConstant,
+    Opcode::OpPop,
+    Opcode::OpAdd,
+    Opcode::OpSub,
+    Opcode::OpMul,
+    Opcode::OpDiv,
+    Opcode::OpTrue,
+    Opcode::OpFalse,
+    Opcode::OpNull,
+    Opcode::OpEqual,
+    Opcode::OpNotEqual,
+    Opcode::OpGreaterThan,
+    Opcode::OpLessThan,
+    Opcode::OpMinus,
+    Opcode::OpBang,
+    Opcode::OpJumpNotTruthy,
+    Opcode::OpJump,
+    Opcode::OpGetGlobal,
+    Opcode::OpSetGlobal,
+    Opcode::OpGetLocal,
+    Opcode::OpSetLocal,
+    Opcode::OpGetFree,
+    Opcode::OpCall,
+    Opcode::OpReturnValue,
+    Opcode::OpReturn,
+    Opcode::OpClosure,
+    Opcode::OpCurrentClosure,
+    Opcode::OpArray,
+    Opcode::OpIndex,
+    Opcode::OpHash,
+];
+
+impl Opcode {
+    pub fn from_byte(b: u8) -> Option<Opcode> {
+        OPCODES.get(b as usize).copied()
+    }
+
+    pub fn definition(self) -> Definition {
+        let (name, operand_widths): (&'static str, &'static [usize]) = match self {
+            Opcode::OpConstant => ("OpConstant", &[2]), // index into the constant pool
+            Opcode::OpPop => ("OpPop", &[]),
+            Opcode::OpAdd => ("OpAdd", &[]),
+            Opcode::OpSub => ("OpSub", &[]),
+            Opcode::OpMul => ("OpMul", &[]),
+            Opcode::OpDiv => ("OpDiv", &[]),
+            Opcode::OpTrue => ("OpTrue", &[]),
+            Opcode::OpFalse => ("OpFalse", &[]),
+            Opcode::OpNull => ("OpNull", &[]),
+            Opcode::OpEqual => ("OpEqual", &[]),
+            Opcode::OpNotEqual => ("OpNotEqual", &[]),
+            Opcode::OpGreaterThan => ("OpGreaterThan", &[]),
+            Opcode::OpLessThan => ("OpLessThan", &[]),
+            Opcode::OpMinus => ("OpMinus", &[]),
+            Opcode::OpBang => ("OpBang", &[]),
+            Opcode::OpJumpNotTruthy => ("OpJumpNotTruthy", &[2]), // absolute jump target
+            Opcode::OpJump => ("OpJump", &[2]),                   // absolute jump target
+            Opcode::OpGetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::OpSetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::OpGetLocal => ("OpGetLocal", &[1]),
+            Opcode::OpSetLocal => ("OpSetLocal", &[1]),
+            Opcode::OpGetFree => ("OpGetFree", &[1]),
+            Opcode::OpCall => ("OpCall", &[1]), // number of arguments
+            Opcode::OpReturnValue => ("OpReturnValue", &[]),
+            Opcode::OpReturn => ("OpReturn", &[]),
+            Opcode::OpClosure => ("OpClosure", &[2, 1]), // constant index, free variable count
+            Opcode::OpCurrentClosure => ("OpCurrentClosure", &[]),
+            Opcode::OpArray => ("OpArray", &[2]), // number of elements
+            Opcode::OpIndex => ("OpIndex", &[]),
+            Opcode::OpHash => ("OpHash", &[2]), // number of keys and values together
+        };
+        Definition {
+            name,
+            operand_widths,
+        }
+    }
+}
+
+// Largest value an operand of the given width can hold
+pub fn max_operand(width: usize) -> usize {
+    (1 << (8 * width)) - 1
+}
+
+// Encode a single instruction, operands are stored big-endian. Operands that don't fit their
+// width are cut down to it, the compiler checks them before.
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    let def = op.definition();
+
+    let instruction_len = 1 + def.operand_widths.iter().sum::<usize>();
+    let mut instruction = Vec::with_capacity(instruction_len);
+    instruction.push(op as u8);
+
+    for (o, width) in operands.iter().zip(def.operand_widths) {
+        match width {
+            2 => instruction.extend_from_slice(&(*o as u16).to_be_bytes()),
+            1 => instruction.push(*o as u8),
+            _ => (),
+        }
+    }
+    instruction
+}
+
+// Decode the operands of an instruction, returning them with the number of bytes read
+pub fn read_operands(def: &Definition, ins: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = Vec::with_capacity(def.operand_widths.len());
+    let mut offset = 0;
+
+    for width in def.operand_widths {
+        match width {
+            2 => operands.push(read_u16(&ins[offset..]) as usize),
+            1 => operands.push(ins[offset] as usize),
+            _ => (),
+        }
+        offset += width;
+    }
+    (operands, offset)
+}
+
+pub fn read_u16(ins: &[u8]) -> u16 {
+    u16::from_be_bytes(ins[..2].try_into().unwrap())
+}
+
+// Human readable disassembly, one instruction per line prefixed with its offset
+pub fn instructions_string(ins: &[u8]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+
+    while i < ins.len() {
+        let op = match Opcode::from_byte(ins[i]) {
+            Some(op) => op,
+            None => {
+                out.push_str(&format!("ERROR: opcode {} undefined\n", ins[i]));
+                i += 1;
+                continue;
+            }
+        };
+        let def = op.definition();
+        let (operands, read) = read_operands(&def, &ins[i + 1..]);
+
+        let mut line = format!("{:04} {}", i, def.name);
+        for o in &operands {
+            line.push_str(&format!(" {}", o));
+        }
+        out.push_str(&line);
+        out.push('\n');
+
+        i += 1 + read;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn make_instructions() {
+        let tests = vec![
+            (
+                Opcode::OpConstant,
+                vec![65534],
+                vec![Opcode::OpConstant as u8, 255, 254],
+            ),
+            (Opcode::OpAdd, vec![], vec![Opcode::OpAdd as u8]),
+            (
+                Opcode::OpGetLocal,
+                vec![255],
+                vec![Opcode::OpGetLocal as u8, 255],
+            ),
+            (
+                Opcode::OpClosure,
+                vec![65534, 255],
+                vec![Opcode::OpClosure as u8, 255, 254, 255],
+            ),
+            (
+                Opcode::OpArray,
+                vec![65535],
+                vec![Opcode::OpArray as u8, 255, 255],
+            ),
+        ];
+
+        for (op, operands, expected) in tests {
+            let instruction = make(op, &operands);
+            assert_eq!(
+                instruction, expected,
+                "instruction has wrong encoding. want={:?}, got={:?}",
+                expected, instruction
+            );
+        }
+    }
+
+    #[test]
+    fn read_operands_roundtrip() {
+        let tests = vec![
+            (Opcode::OpConstant, vec![65535], 2),
+            (Opcode::OpGetLocal, vec![255], 1),
+            (Opcode::OpClosure, vec![65535, 255], 3),
+        ];
+
+        for (op, operands, bytes_read) in tests {
+            let instruction = make(op, &operands);
+            let def = op.definition();
+            let (operands_read, n) = read_operands(&def, &instruction[1..]);
+            assert_eq!(n, bytes_read, "n wrong. want={}, got={}", bytes_read, n);
+            assert_eq!(operands_read, operands);
+        }
+    }
+
+    #[test]
+    fn instructions_to_string() {
+        let instructions = [
+            make(Opcode::OpAdd, &[]),
+            make(Opcode::OpGetLocal, &[1]),
+            make(Opcode::OpConstant, &[2]),
+            make(Opcode::OpConstant, &[65535]),
+            make(Opcode::OpClosure, &[65535, 255]),
+        ]
+        .concat();
+
+        let expected = "0000 OpAdd
+0001 OpGetLocal 1
+0003 OpConstant 2
+0006 OpConstant 65535
+0009 OpClosure 65535 255
+";
+        assert_eq!(instructions_string(&instructions), expected);
+    }
+}
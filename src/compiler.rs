@@ -0,0 +1,918 @@
+use crate::ast::*;
+use crate::code::*;
+use crate::integer::Integer;
+use crate::object::*;
+use crate::symbol_table::*;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Operand of a jump that is patched once the real target is known
+const PLACEHOLDER_JUMP: usize = 9999;
+
+pub struct Bytecode<'a> {
+    pub instructions: Instructions,
+    pub constants: Vec<Object<'a>>,
+    pub global_names: Vec<String>, // Indexed by global, to report the ones read before a `let`
+}
+
+#[derive(Clone, Copy)]
+struct EmittedInstruction {
+    opcode: Opcode,
+    position: usize,
+}
+
+// Instructions of the function body being compiled, the main program is the outermost scope
+#[derive(Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    last_instruction: Option<EmittedInstruction>,
+    previous_instruction: Option<EmittedInstruction>,
+}
+
+// Literals with the same value share one slot of the constant pool
+#[derive(Eq, PartialEq, Hash)]
+enum ConstantKey {
+    Integer(Integer),
+    Float(u64), // Bits of the value, a literal is never NaN
+    String(String),
+}
+
+impl ConstantKey {
+    fn of(obj: &Object) -> Option<ConstantKey> {
+        match obj {
+            Object::Integer(x) => Some(ConstantKey::Integer(x.to_owned())),
+            Object::Float(x) => Some(ConstantKey::Float(x.to_bits())),
+            Object::String(x) => Some(ConstantKey::String(x.clone())),
+            _ => None,
+        }
+    }
+}
+
+pub struct Compiler<'a> {
+    constants: Vec<Object<'a>>,
+    constant_indexes: HashMap<ConstantKey, usize>,
+    symbol_table: SymbolTable,
+    scopes: Vec<CompilationScope>,
+}
+
+impl<'a> Compiler<'a> {
+    pub fn new() -> Compiler<'a> {
+        Compiler::new_with_state(SymbolTable::new(), vec![])
+    }
+
+    // Continue where an earlier compiler left off, used by the REPL to keep its bindings
+    pub fn new_with_state(symbol_table: SymbolTable, constants: Vec<Object<'a>>) -> Compiler<'a> {
+        let constant_indexes = constants
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| ConstantKey::of(c).map(|key| (key, i)))
+            .collect();
+        Compiler {
+            constants,
+            constant_indexes,
+            symbol_table,
+            scopes: vec![CompilationScope::default()],
+        }
+    }
+
+    pub fn into_state(self) -> (SymbolTable, Vec<Object<'a>>) {
+        (self.symbol_table, self.constants)
+    }
+
+    // The result of the program is the last value popped. A program ending in a `let` has no
+    // value, like in the evaluator, so a null is popped after it.
+    pub fn compile(&mut self, program: &Program) -> Result<(), String> {
+        for s in &program.statements {
+            self.compile_statement(s)?;
+        }
+        if let Some(Statement::LetStatement(_)) = program.statements.last() {
+            self.emit(Opcode::OpNull, &[])?;
+            self.emit(Opcode::OpPop, &[])?;
+        }
+        Ok(())
+    }
+
+    pub fn bytecode(&self) -> Bytecode<'a> {
+        let mut global_names = vec![String::new(); self.symbol_table.num_definitions];
+        for s in self.symbol_table.symbols() {
+            global_names[s.index] = s.name.clone();
+        }
+
+        Bytecode {
+            instructions: self.current_instructions().clone(),
+            constants: self.constants.clone(),
+            global_names,
+        }
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) -> Result<(), String> {
+        match statement {
+            Statement::ExpressionStatement(x) => {
+                self.compile_expression(&x.expression)?;
+                self.emit(Opcode::OpPop, &[])?;
+            }
+            Statement::LetStatement(x) => {
+                match &x.value {
+                    Expression::FunctionLiteral(f) => {
+                        self.compile_function_literal(f, Some(&x.name.value))?
+                    }
+                    value => self.compile_expression(value)?,
+                }
+                let symbol = self.symbol_table.define(&x.name.value);
+                match symbol.scope {
+                    SymbolScope::Global => self.emit(Opcode::OpSetGlobal, &[symbol.index])?,
+                    _ => self.emit(Opcode::OpSetLocal, &[symbol.index])?,
+                };
+            }
+            Statement::ReturnStatement(x) => {
+                self.compile_expression(&x.return_value)?;
+                self.emit(Opcode::OpReturnValue, &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_block_statement(&mut self, block: &BlockStatement) -> Result<(), String> {
+        for s in &block.statements {
+            self.compile_statement(s)?;
+        }
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expression: &Expression) -> Result<(), String> {
+        match expression {
+            Expression::IntegerLiteral(x) => {
+                let index = self.add_constant(Object::Integer(x.value.to_owned()));
+                self.emit(Opcode::OpConstant, &[index])?;
+            }
+            Expression::FloatLiteral(x) => {
+                let index = self.add_constant(Object::Float(x.value));
+                self.emit(Opcode::OpConstant, &[index])?;
+            }
+            Expression::StringLiteral(x) => {
+                let index = self.add_constant(Object::String(x.value.clone()));
+                self.emit(Opcode::OpConstant, &[index])?;
+            }
+            Expression::Boolean(x) => {
+                if x.value {
+                    self.emit(Opcode::OpTrue, &[])?;
+                } else {
+                    self.emit(Opcode::OpFalse, &[])?;
+                }
+            }
+            // A name without a binding yet, builtins included, is a global that a later `let`
+            // may still bind. The VM falls back to the builtin of that name while it is unset.
+            Expression::Identifier(x) => {
+                let symbol = match self.symbol_table.resolve(&x.value) {
+                    Some(symbol) => symbol,
+                    None => self.symbol_table.declare_global(&x.value),
+                };
+                self.load_symbol(&symbol)?;
+            }
+            Expression::PrefixExpression(x) => {
+                self.compile_expression(&x.right)?;
+                match x.operator.as_str() {
+                    "!" => self.emit(Opcode::OpBang, &[])?,
+                    "-" => self.emit(Opcode::OpMinus, &[])?,
+                    op => return Err(format!("unknown operator {}", op)),
+                };
+            }
+            Expression::InfixExpression(x) => {
+                self.compile_expression(&x.left)?;
+                self.compile_expression(&x.right)?;
+                match x.operator.as_str() {
+                    "+" => self.emit(Opcode::OpAdd, &[])?,
+                    "-" => self.emit(Opcode::OpSub, &[])?,
+                    "*" => self.emit(Opcode::OpMul, &[])?,
+                    "/" => self.emit(Opcode::OpDiv, &[])?,
+                    ">" => self.emit(Opcode::OpGreaterThan, &[])?,
+                    "<" => self.emit(Opcode::OpLessThan, &[])?,
+                    "==" => self.emit(Opcode::OpEqual, &[])?,
+                    "!=" => self.emit(Opcode::OpNotEqual, &[])?,
+                    op => return Err(format!("unknown operator {}", op)),
+                };
+            }
+            Expression::IfExpression(x) => self.compile_if_expression(x)?,
+            Expression::FunctionLiteral(x) => self.compile_function_literal(x, None)?,
+            Expression::CallExpression(x) => {
+                self.compile_expression(&x.function)?;
+                for a in &x.arguments {
+                    self.compile_expression(a)?;
+                }
+                self.emit(Opcode::OpCall, &[x.arguments.len()])?;
+            }
+            Expression::ArrayLiteral(x) => {
+                for e in &x.elements {
+                    self.compile_expression(e)?;
+                }
+                self.emit(Opcode::OpArray, &[x.elements.len()])?;
+            }
+            Expression::HashLiteral(x) => {
+                for (key, value) in &x.pairs {
+                    self.compile_expression(key)?;
+                    self.compile_expression(value)?;
+                }
+                self.emit(Opcode::OpHash, &[x.pairs.len() * 2])?;
+            }
+            Expression::IndexExpression(x) => {
+                self.compile_expression(&x.left)?;
+                self.compile_expression(&x.index)?;
+                self.emit(Opcode::OpIndex, &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    // Both branches leave exactly one value on the stack, a missing else produces null
+    fn compile_if_expression(&mut self, ie: &IfExpression) -> Result<(), String> {
+        self.compile_expression(&ie.condition)?;
+
+        let jump_not_truthy_pos = self.emit(Opcode::OpJumpNotTruthy, &[PLACEHOLDER_JUMP])?;
+
+        self.compile_block_statement(&ie.consequence)?;
+        self.keep_block_value()?;
+
+        let jump_pos = self.emit(Opcode::OpJump, &[PLACEHOLDER_JUMP])?;
+
+        let after_consequence_pos = self.current_instructions().len();
+        self.change_operand(jump_not_truthy_pos, after_consequence_pos)?;
+
+        match &ie.alternative {
+            Some(alternative) => {
+                self.compile_block_statement(alternative)?;
+                self.keep_block_value()?;
+            }
+            None => {
+                self.emit(Opcode::OpNull, &[])?;
+            }
+        }
+
+        let after_alternative_pos = self.current_instructions().len();
+        self.change_operand(jump_pos, after_alternative_pos)?;
+        Ok(())
+    }
+
+    // `name` is the binding of a `let` the literal is assigned to, which lets the body refer
+    // to the function itself
+    fn compile_function_literal(
+        &mut self,
+        function: &FunctionLiteral,
+        name: Option<&str>,
+    ) -> Result<(), String> {
+        self.enter_scope();
+
+        if let Some(name) = name {
+            self.symbol_table.define_function_name(name);
+        }
+
+        for p in &function.parameters {
+            self.symbol_table.define(&p.value);
+        }
+
+        // The scope is left on errors too, the REPL keeps using the symbol table
+        let compiled = self.compile_function_body(&function.body);
+        let free_symbols = self.symbol_table.free_symbols.clone();
+        let num_locals = self.symbol_table.num_definitions;
+        let instructions = self.leave_scope();
+        compiled?;
+
+        for s in &free_symbols {
+            self.load_symbol(s)?;
+        }
+
+        let compiled_fn = CompiledFunction {
+            instructions,
+            num_locals,
+            num_parameters: function.parameters.len(),
+            source: function_source(&function.parameters, &function.body),
+        };
+        let index = self.add_constant(Object::CompiledFunction(Rc::new(compiled_fn)));
+        self.emit(Opcode::OpClosure, &[index, free_symbols.len()])?;
+        Ok(())
+    }
+
+    fn compile_function_body(&mut self, body: &BlockStatement) -> Result<(), String> {
+        self.compile_block_statement(body)?;
+
+        if self.last_instruction_is(Opcode::OpPop) {
+            self.replace_last_pop_with_return();
+        }
+        if !self.last_instruction_is(Opcode::OpReturnValue) {
+            self.emit(Opcode::OpReturn, &[])?;
+        }
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, s: &Symbol) -> Result<(), String> {
+        match s.scope {
+            SymbolScope::Global => self.emit(Opcode::OpGetGlobal, &[s.index])?,
+            SymbolScope::Local => self.emit(Opcode::OpGetLocal, &[s.index])?,
+            SymbolScope::Free => self.emit(Opcode::OpGetFree, &[s.index])?,
+            SymbolScope::Function => self.emit(Opcode::OpCurrentClosure, &[])?,
+        };
+        Ok(())
+    }
+
+    fn add_constant(&mut self, obj: Object<'a>) -> usize {
+        let key = ConstantKey::of(&obj);
+        if let Some(index) = key.as_ref().and_then(|k| self.constant_indexes.get(k)) {
+            return *index;
+        }
+
+        let index = self.constants.len();
+        self.constants.push(obj);
+        if let Some(key) = key {
+            self.constant_indexes.insert(key, index);
+        }
+        index
+    }
+
+    // Append the instruction to the current scope and return its position
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> Result<usize, String> {
+        check_operands(op, operands)?;
+        let ins = make(op, operands);
+        let scope = self.current_scope();
+        let position = scope.instructions.len();
+        scope.instructions.extend(ins);
+
+        scope.previous_instruction = scope.last_instruction;
+        scope.last_instruction = Some(EmittedInstruction {
+            opcode: op,
+            position,
+        });
+        Ok(position)
+    }
+
+    // The value of a block is its last expression, so it must stay on the stack. A block that
+    // ends without an expression evaluates to null.
+    fn keep_block_value(&mut self) -> Result<(), String> {
+        if self.last_instruction_is(Opcode::OpPop) {
+            self.remove_last_pop();
+        } else {
+            self.emit(Opcode::OpNull, &[])?;
+        }
+        Ok(())
+    }
+
+    fn last_instruction_is(&self, op: Opcode) -> bool {
+        match self.scopes.last().unwrap().last_instruction {
+            Some(last) => last.opcode == op,
+            None => false,
+        }
+    }
+
+    fn remove_last_pop(&mut self) {
+        let scope = self.current_scope();
+        let last = scope.last_instruction.unwrap();
+
+        scope.instructions.truncate(last.position);
+        scope.last_instruction = scope.previous_instruction;
+    }
+
+    fn replace_last_pop_with_return(&mut self) {
+        let last_pos = self.current_scope().last_instruction.unwrap().position;
+        self.replace_instruction(last_pos, make(Opcode::OpReturnValue, &[]));
+        self.current_scope().last_instruction = Some(EmittedInstruction {
+            opcode: Opcode::OpReturnValue,
+            position: last_pos,
+        });
+    }
+
+    fn replace_instruction(&mut self, position: usize, new_instruction: Instructions) {
+        let ins = &mut self.current_scope().instructions;
+        ins[position..position + new_instruction.len()].copy_from_slice(&new_instruction);
+    }
+
+    fn change_operand(&mut self, op_position: usize, operand: usize) -> Result<(), String> {
+        let op = Opcode::from_byte(self.current_instructions()[op_position]).unwrap();
+        check_operands(op, &[operand])?;
+        self.replace_instruction(op_position, make(op, &[operand]));
+        Ok(())
+    }
+
+    fn current_scope(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().unwrap()
+    }
+
+    fn current_instructions(&self) -> &Instructions {
+        &self.scopes.last().unwrap().instructions
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+        let outer = std::mem::take(&mut self.symbol_table);
+        self.symbol_table = SymbolTable::new_enclosed(outer);
+    }
+
+    fn leave_scope(&mut self) -> Instructions {
+        let scope = self.scopes.pop().unwrap();
+        let outer = self.symbol_table.outer.take().unwrap();
+        self.symbol_table = *outer;
+        scope.instructions
+    }
+}
+
+// Operands have a fixed width in the bytecode, programs that need larger ones can't be compiled
+fn check_operands(op: Opcode, operands: &[usize]) -> Result<(), String> {
+    let widths = op.definition().operand_widths;
+
+    for (i, (operand, width)) in operands.iter().zip(widths).enumerate() {
+        let max = max_operand(*width);
+        if *operand <= max {
+            continue;
+        }
+        return Err(match (op, i) {
+            (Opcode::OpConstant, _) | (Opcode::OpClosure, 0) => {
+                format!("too many constants, the maximum is {}", max + 1)
+            }
+            (Opcode::OpGetGlobal, _) | (Opcode::OpSetGlobal, _) => {
+                format!("too many global bindings, the maximum is {}", max + 1)
+            }
+            (Opcode::OpGetLocal, _) | (Opcode::OpSetLocal, _) => format!(
+                "too many local bindings in a function, the maximum is {}",
+                max + 1
+            ),
+            (Opcode::OpGetFree, _) | (Opcode::OpClosure, _) => format!(
+                "too many free variables in a function, the maximum is {}",
+                max
+            ),
+            (Opcode::OpJump, _) | (Opcode::OpJumpNotTruthy, _) => {
+                format!("too many instructions, jumps can't go past offset {}", max)
+            }
+            (Opcode::OpArray, _) => format!("too many array elements, the maximum is {}", max),
+            (Opcode::OpHash, _) => format!("too many hash pairs, the maximum is {}", max / 2),
+            (Opcode::OpCall, _) => format!("too many arguments, the maximum is {}", max),
+            _ => format!(
+                "operand {} of {} does not fit in {} bytes",
+                operand,
+                op.definition().name,
+                width
+            ),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    enum Constant {
+        Int(i64),
+        Float(f64),
+        Str(&'static str),
+        Function(Vec<Instructions>),
+    }
+
+    fn run_compiler_test(
+        input: &str,
+        expected_constants: Vec<Constant>,
+        expected: Vec<Instructions>,
+    ) {
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+
+        let mut compiler = Compiler::new();
+        if let Err(err) = compiler.compile(&program) {
+            panic!("compiler error: {}", err);
+        }
+        let bytecode = compiler.bytecode();
+
+        let expected = expected.concat();
+        assert_eq!(
+            bytecode.instructions,
+            expected,
+            "wrong instructions.\nwant=\n{}\ngot=\n{}",
+            instructions_string(&expected),
+            instructions_string(&bytecode.instructions)
+        );
+
+        assert_eq!(
+            bytecode.constants.len(),
+            expected_constants.len(),
+            "wrong number of constants. got={:?}",
+            bytecode.constants
+        );
+        for (constant, expected) in bytecode.constants.iter().zip(expected_constants) {
+            match (constant, expected) {
+                (Object::Integer(x), Constant::Int(want)) => {
+                    assert_eq!(*x, integer::from_i64(want))
+                }
+                (Object::Float(x), Constant::Float(want)) => assert_eq!(*x, want),
+                (Object::String(x), Constant::Str(want)) => assert_eq!(x, want),
+                (Object::CompiledFunction(f), Constant::Function(want)) => {
+                    let want = want.concat();
+                    assert_eq!(
+                        f.instructions,
+                        want,
+                        "wrong function instructions.\nwant=\n{}\ngot=\n{}",
+                        instructions_string(&want),
+                        instructions_string(&f.instructions)
+                    );
+                }
+                (constant, _) => panic!("constant has wrong type. got={:?}", constant),
+            }
+        }
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        run_compiler_test(
+            "1 + 2",
+            vec![Constant::Int(1), Constant::Int(2)],
+            vec![
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpAdd, &[]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+        run_compiler_test(
+            "-1 < 2",
+            vec![Constant::Int(1), Constant::Int(2)],
+            vec![
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpMinus, &[]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpLessThan, &[]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn float_arithmetic() {
+        run_compiler_test(
+            "1.5 * 2",
+            vec![Constant::Float(1.5), Constant::Int(2)],
+            vec![
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpMul, &[]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn conditionals() {
+        run_compiler_test(
+            "if (true) { 10 }; 3333;",
+            vec![Constant::Int(10), Constant::Int(3333)],
+            vec![
+                make(Opcode::OpTrue, &[]),
+                make(Opcode::OpJumpNotTruthy, &[10]),
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpJump, &[11]),
+                make(Opcode::OpNull, &[]),
+                make(Opcode::OpPop, &[]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+        run_compiler_test(
+            "if (true) { 10 } else { 20 }",
+            vec![Constant::Int(10), Constant::Int(20)],
+            vec![
+                make(Opcode::OpTrue, &[]),
+                make(Opcode::OpJumpNotTruthy, &[10]),
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpJump, &[13]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn string_expressions() {
+        run_compiler_test(
+            r#""mon" + "key""#,
+            vec![Constant::Str("mon"), Constant::Str("key")],
+            vec![
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpAdd, &[]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn array_literals() {
+        run_compiler_test(
+            "[]",
+            vec![],
+            vec![make(Opcode::OpArray, &[0]), make(Opcode::OpPop, &[])],
+        );
+        run_compiler_test(
+            "[1, 2 + 3]",
+            vec![Constant::Int(1), Constant::Int(2), Constant::Int(3)],
+            vec![
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpConstant, &[2]),
+                make(Opcode::OpAdd, &[]),
+                make(Opcode::OpArray, &[2]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn hash_literals() {
+        run_compiler_test(
+            "{}",
+            vec![],
+            vec![make(Opcode::OpHash, &[0]), make(Opcode::OpPop, &[])],
+        );
+        run_compiler_test(
+            "{1: 2, 3: 4 * 5}",
+            vec![
+                Constant::Int(1),
+                Constant::Int(2),
+                Constant::Int(3),
+                Constant::Int(4),
+                Constant::Int(5),
+            ],
+            vec![
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpConstant, &[2]),
+                make(Opcode::OpConstant, &[3]),
+                make(Opcode::OpConstant, &[4]),
+                make(Opcode::OpMul, &[]),
+                make(Opcode::OpHash, &[4]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn index_expressions() {
+        run_compiler_test(
+            "[1, 2][1 + 1]",
+            vec![Constant::Int(1), Constant::Int(2)],
+            vec![
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpArray, &[2]),
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpAdd, &[]),
+                make(Opcode::OpIndex, &[]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+    }
+
+    // Builtins are read through globals, a later `let` can still rebind them
+    #[test]
+    fn builtins() {
+        run_compiler_test(
+            "len([]); push([], 1);",
+            vec![Constant::Int(1)],
+            vec![
+                make(Opcode::OpGetGlobal, &[0]),
+                make(Opcode::OpArray, &[0]),
+                make(Opcode::OpCall, &[1]),
+                make(Opcode::OpPop, &[]),
+                make(Opcode::OpGetGlobal, &[1]),
+                make(Opcode::OpArray, &[0]),
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpCall, &[2]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+        run_compiler_test(
+            "fn() { len([]) }",
+            vec![Constant::Function(vec![
+                make(Opcode::OpGetGlobal, &[0]),
+                make(Opcode::OpArray, &[0]),
+                make(Opcode::OpCall, &[1]),
+                make(Opcode::OpReturnValue, &[]),
+            ])],
+            vec![make(Opcode::OpClosure, &[0, 0]), make(Opcode::OpPop, &[])],
+        );
+        run_compiler_test(
+            "let len = 1; len",
+            vec![Constant::Int(1)],
+            vec![
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpSetGlobal, &[0]),
+                make(Opcode::OpGetGlobal, &[0]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn global_let_statements() {
+        run_compiler_test(
+            "let one = 1; one;",
+            vec![Constant::Int(1)],
+            vec![
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpSetGlobal, &[0]),
+                make(Opcode::OpGetGlobal, &[0]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn closures() {
+        run_compiler_test(
+            "fn(a) { fn(b) { a + b } }",
+            vec![
+                Constant::Function(vec![
+                    make(Opcode::OpGetFree, &[0]),
+                    make(Opcode::OpGetLocal, &[0]),
+                    make(Opcode::OpAdd, &[]),
+                    make(Opcode::OpReturnValue, &[]),
+                ]),
+                Constant::Function(vec![
+                    make(Opcode::OpGetLocal, &[0]),
+                    make(Opcode::OpClosure, &[0, 1]),
+                    make(Opcode::OpReturnValue, &[]),
+                ]),
+            ],
+            vec![make(Opcode::OpClosure, &[1, 0]), make(Opcode::OpPop, &[])],
+        );
+    }
+
+    #[test]
+    fn recursive_functions() {
+        run_compiler_test(
+            "let countDown = fn(x) { countDown(x - 1); }; countDown(1);",
+            vec![
+                Constant::Int(1),
+                Constant::Function(vec![
+                    make(Opcode::OpCurrentClosure, &[]),
+                    make(Opcode::OpGetLocal, &[0]),
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpSub, &[]),
+                    make(Opcode::OpCall, &[1]),
+                    make(Opcode::OpReturnValue, &[]),
+                ]),
+            ],
+            vec![
+                make(Opcode::OpClosure, &[1, 0]),
+                make(Opcode::OpSetGlobal, &[0]),
+                make(Opcode::OpGetGlobal, &[0]),
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpCall, &[1]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn shared_constants() {
+        run_compiler_test(
+            r#"1; "a"; 1.5; 1; "a"; 1.5; 2"#,
+            vec![
+                Constant::Int(1),
+                Constant::Str("a"),
+                Constant::Float(1.5),
+                Constant::Int(2),
+            ],
+            vec![
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpPop, &[]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpPop, &[]),
+                make(Opcode::OpConstant, &[2]),
+                make(Opcode::OpPop, &[]),
+                make(Opcode::OpConstant, &[0]),
+                make(Opcode::OpPop, &[]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpPop, &[]),
+                make(Opcode::OpConstant, &[2]),
+                make(Opcode::OpPop, &[]),
+                make(Opcode::OpConstant, &[3]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+    }
+
+    fn compile_error(input: &str) -> Option<String> {
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+
+        Compiler::new().compile(&program).err()
+    }
+
+    // Name spelled with letters only for a number, 0 is `xa` and 27 is `xbb`
+    fn name(mut i: usize) -> String {
+        let mut letters = vec![];
+        loop {
+            letters.push((b'a' + (i % 26) as u8) as char);
+            i /= 26;
+            if i == 0 {
+                break;
+            }
+        }
+        letters.push('x');
+        letters.iter().rev().collect()
+    }
+
+    // The first n names or numbers joined by `separator`
+    fn list(n: usize, item: fn(usize) -> String, separator: &str) -> String {
+        let items: Vec<String> = (0..n).map(item).collect();
+        items.join(separator)
+    }
+
+    #[test]
+    fn operand_limits() {
+        let constants = |n: usize| {
+            let statements: Vec<String> = (0..n).map(|i| format!("{};", i)).collect();
+            statements.concat()
+        };
+        let args = |n| format!("fn() {{}}({})", list(n, |i| i.to_string(), ", "));
+        let locals = |n| format!("fn({}) {{ {} }}", list(n, name, ", "), name(n - 1));
+        let globals = |n: usize| {
+            let lets: Vec<String> = (0..n).map(|i| format!("let {} = 0;", name(i))).collect();
+            lets.concat()
+        };
+        let free = |n| {
+            format!(
+                "fn({}) {{ fn() {{ {} }} }}",
+                list(n, name, ", "),
+                list(n, name, " + ")
+            )
+        };
+        let jump = |n| format!("{} if (true) {{ 1 }}", "1;".repeat(n));
+
+        let tests: Vec<(String, Option<&str>)> = vec![
+            (constants(65536), None),
+            (
+                constants(65537),
+                Some("too many constants, the maximum is 65536"),
+            ),
+            (args(255), None),
+            (args(256), Some("too many arguments, the maximum is 255")),
+            (locals(256), None),
+            (
+                locals(257),
+                Some("too many local bindings in a function, the maximum is 256"),
+            ),
+            (globals(65536), None),
+            (
+                globals(65537),
+                Some("too many global bindings, the maximum is 65536"),
+            ),
+            (free(255), None),
+            (
+                free(256),
+                Some("too many free variables in a function, the maximum is 255"),
+            ),
+            // Each `1;` is 4 bytes, the jump past the missing else then lands at 65535
+            (jump(16381), None),
+            (
+                jump(16382),
+                Some("too many instructions, jumps can't go past offset 65535"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let err = compile_error(&input);
+            assert_eq!(
+                err.as_deref(),
+                expected,
+                "wrong result for input of length {}",
+                input.len()
+            );
+        }
+    }
+
+    #[test]
+    fn undefined_identifiers() {
+        run_compiler_test(
+            "let f = fn() { g() }; let g = 1; foobar",
+            vec![
+                Constant::Function(vec![
+                    make(Opcode::OpGetGlobal, &[0]),
+                    make(Opcode::OpCall, &[0]),
+                    make(Opcode::OpReturnValue, &[]),
+                ]),
+                Constant::Int(1),
+            ],
+            vec![
+                make(Opcode::OpClosure, &[0, 0]),
+                make(Opcode::OpSetGlobal, &[1]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpSetGlobal, &[0]),
+                make(Opcode::OpGetGlobal, &[2]),
+                make(Opcode::OpPop, &[]),
+            ],
+        );
+    }
+}
@@ -0,0 +1,384 @@
+use crate::eval;
+use crate::integer;
+use crate::object::*;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
+
+// Rust values that can be handed to a Monkey program
+pub trait IntoObject<'a> {
+    fn into_object(self) -> Object<'a>;
+}
+
+// Rust values that can be taken out of a Monkey program. The error says which type was
+// expected, e.g. "expected INTEGER, got STRING".
+pub trait FromObject<'a>: Sized {
+    fn from_object(obj: &Object<'a>) -> Result<Self, String>;
+}
+
+fn expected<T>(want: &str, obj: &Object) -> Result<T, String> {
+    Err(format!("expected {}, got {}", want, obj.object_type()))
+}
+
+impl<'a> IntoObject<'a> for Object<'a> {
+    fn into_object(self) -> Object<'a> {
+        self
+    }
+}
+
+impl<'a> FromObject<'a> for Object<'a> {
+    fn from_object(obj: &Object<'a>) -> Result<Self, String> {
+        Ok(obj.clone())
+    }
+}
+
+impl<'a> IntoObject<'a> for () {
+    fn into_object(self) -> Object<'a> {
+        Object::Null
+    }
+}
+
+// Accepts any value, for results that are not needed
+impl<'a> FromObject<'a> for () {
+    fn from_object(_obj: &Object<'a>) -> Result<Self, String> {
+        Ok(())
+    }
+}
+
+impl<'a> IntoObject<'a> for i64 {
+    fn into_object(self) -> Object<'a> {
+        Object::Integer(integer::from_i64(self))
+    }
+}
+
+// Fails for the integers of the `bigint` feature that are too large
+impl<'a> FromObject<'a> for i64 {
+    fn from_object(obj: &Object<'a>) -> Result<Self, String> {
+        match obj {
+            Object::Integer(x) => {
+                integer::to_i64(x).ok_or_else(|| format!("integer {} does not fit in i64", x))
+            }
+            _ => expected(INTEGER_OBJ, obj),
+        }
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl<'a> IntoObject<'a> for num_bigint::BigInt {
+    fn into_object(self) -> Object<'a> {
+        Object::Integer(self)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl<'a> FromObject<'a> for num_bigint::BigInt {
+    fn from_object(obj: &Object<'a>) -> Result<Self, String> {
+        match obj {
+            Object::Integer(x) => Ok(x.clone()),
+            _ => expected(INTEGER_OBJ, obj),
+        }
+    }
+}
+
+impl<'a> IntoObject<'a> for f64 {
+    fn into_object(self) -> Object<'a> {
+        Object::Float(self)
+    }
+}
+
+// Integers are converted too, the same way arithmetic with a float converts them
+impl<'a> FromObject<'a> for f64 {
+    fn from_object(obj: &Object<'a>) -> Result<Self, String> {
+        match obj {
+            Object::Float(x) => Ok(*x),
+            Object::Integer(x) => Ok(integer::to_f64(x)),
+            _ => expected(FLOAT_OBJ, obj),
+        }
+    }
+}
+
+impl<'a> IntoObject<'a> for bool {
+    fn into_object(self) -> Object<'a> {
+        Object::Boolean(self)
+    }
+}
+
+impl<'a> FromObject<'a> for bool {
+    fn from_object(obj: &Object<'a>) -> Result<Self, String> {
+        match obj {
+            Object::Boolean(x) => Ok(*x),
+            _ => expected(BOOLEAN_OBJ, obj),
+        }
+    }
+}
+
+impl<'a> IntoObject<'a> for String {
+    fn into_object(self) -> Object<'a> {
+        Object::String(self)
+    }
+}
+
+impl<'a> IntoObject<'a> for &str {
+    fn into_object(self) -> Object<'a> {
+        Object::String(self.to_string())
+    }
+}
+
+impl<'a> FromObject<'a> for String {
+    fn from_object(obj: &Object<'a>) -> Result<Self, String> {
+        match obj {
+            Object::String(x) => Ok(x.clone()),
+            _ => expected(STRING_OBJ, obj),
+        }
+    }
+}
+
+impl<'a, T: IntoObject<'a>> IntoObject<'a> for Vec<T> {
+    fn into_object(self) -> Object<'a> {
+        let elements = self.into_iter().map(|e| e.into_object()).collect();
+        Object::Array(Rc::new(elements))
+    }
+}
+
+impl<'a, T: FromObject<'a>> FromObject<'a> for Vec<T> {
+    fn from_object(obj: &Object<'a>) -> Result<Self, String> {
+        match obj {
+            Object::Array(x) => x.iter().map(T::from_object).collect(),
+            _ => expected(ARRAY_OBJ, obj),
+        }
+    }
+}
+
+// Keys that are not integers, booleans or strings turn the hash into an error
+impl<'a, K: IntoObject<'a>, V: IntoObject<'a>> IntoObject<'a> for HashMap<K, V> {
+    fn into_object(self) -> Object<'a> {
+        let mut keys_and_values = vec![];
+        for (k, v) in self {
+            keys_and_values.push(k.into_object());
+            keys_and_values.push(v.into_object());
+        }
+        eval::build_hash(&keys_and_values)
+    }
+}
+
+impl<'a, K, V> FromObject<'a> for HashMap<K, V>
+where
+    K: FromObject<'a> + Eq + Hash,
+    V: FromObject<'a>,
+{
+    fn from_object(obj: &Object<'a>) -> Result<Self, String> {
+        match obj {
+            Object::Hash(x) => x
+                .values()
+                .map(|pair| Ok((K::from_object(&pair.key)?, V::from_object(&pair.value)?)))
+                .collect(),
+            _ => expected(HASH_OBJ, obj),
+        }
+    }
+}
+
+// None is null
+impl<'a, T: IntoObject<'a>> IntoObject<'a> for Option<T> {
+    fn into_object(self) -> Object<'a> {
+        match self {
+            Some(x) => x.into_object(),
+            None => Object::Null,
+        }
+    }
+}
+
+impl<'a, T: FromObject<'a>> FromObject<'a> for Option<T> {
+    fn from_object(obj: &Object<'a>) -> Result<Self, String> {
+        match obj {
+            Object::Null => Ok(None),
+            _ => T::from_object(obj).map(Some),
+        }
+    }
+}
+
+// Lets host functions fail, the message becomes a runtime error of the Monkey program
+impl<'a, T: IntoObject<'a>> IntoObject<'a> for Result<T, String> {
+    fn into_object(self) -> Object<'a> {
+        match self {
+            Ok(x) => x.into_object(),
+            Err(msg) => Object::Error(msg),
+        }
+    }
+}
+
+// Rust closures callable from Monkey. `Args` is the tuple of the parameter types, so a
+// closure taking (i64, String) implements HostFn<(i64, String)>. Arguments are converted with
+// FromObject and the result with IntoObject.
+pub trait HostFn<'a, Args> {
+    fn call(&self, name: &str, args: &[Object<'a>]) -> Object<'a>;
+}
+
+macro_rules! impl_host_fn {
+    ($($arg:ident $var:ident),*) => {
+        impl<'a, F, R, $($arg),*> HostFn<'a, ($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R,
+            R: IntoObject<'a>,
+            $($arg: FromObject<'a>,)*
+        {
+            #[allow(unused_variables, unused_mut)]
+            fn call(&self, name: &str, args: &[Object<'a>]) -> Object<'a> {
+                let want = <[&str]>::len(&[$(stringify!($arg)),*]);
+                if args.len() != want {
+                    return Object::Error(format!(
+                        "wrong number of arguments: want={}, got={}",
+                        want,
+                        args.len()
+                    ));
+                }
+
+                let mut position = 0;
+                $(
+                    position += 1;
+                    let $var = match $arg::from_object(&args[position - 1]) {
+                        Ok(value) => value,
+                        Err(msg) => {
+                            return Object::Error(format!(
+                                "argument {} to `{}`: {}",
+                                position, name, msg
+                            ))
+                        }
+                    };
+                )*
+                self($($var),*).into_object()
+            }
+        }
+    };
+}
+
+impl_host_fn!();
+impl_host_fn!(A a);
+impl_host_fn!(A a, B b);
+impl_host_fn!(A a, B b, C c);
+impl_host_fn!(A a, B b, C c, D d);
+impl_host_fn!(A a, B b, C c, D d, E e);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip<'a, T: IntoObject<'a> + FromObject<'a> + Clone>(value: T) -> T {
+        T::from_object(&value.into_object()).unwrap()
+    }
+
+    #[test]
+    fn roundtrips() {
+        assert_eq!(roundtrip(-7i64), -7);
+        assert!(roundtrip(true));
+        assert_eq!(roundtrip(2.5f64), 2.5);
+        assert_eq!(roundtrip(String::from("été")), "été");
+        assert_eq!(roundtrip(vec![1i64, 2, 3]), vec![1, 2, 3]);
+        assert_eq!(
+            roundtrip(vec![vec![String::from("a")], vec![]]),
+            vec![vec![String::from("a")], vec![]]
+        );
+        assert_eq!(roundtrip(Some(5i64)), Some(5));
+        assert_eq!(roundtrip(None::<i64>), None);
+
+        let mut map = HashMap::new();
+        map.insert(String::from("one"), vec![1i64]);
+        map.insert(String::from("two"), vec![2, 2]);
+        assert_eq!(roundtrip(map.clone()), map);
+    }
+
+    #[test]
+    fn into_objects() {
+        let mut map = HashMap::new();
+        map.insert(1i64, "one");
+
+        let tests = vec![
+            ("x".into_object(), "x"),
+            (().into_object(), "null"),
+            (vec![Some(true), None].into_object(), "[true, null]"),
+            (map.into_object(), "{1: one}"),
+            (Ok::<i64, String>(1).into_object(), "1"),
+            (
+                Err::<i64, String>(String::from("no")).into_object(),
+                "ERROR: no",
+            ),
+        ];
+
+        for (obj, expected) in tests {
+            assert_eq!(obj.inspect(), expected);
+        }
+
+        let mut unhashable = HashMap::new();
+        unhashable.insert(Some(1i64), 1i64);
+        unhashable.insert(None, 2);
+        assert!(unhashable.into_object().is_error());
+    }
+
+    #[test]
+    fn conversion_errors() {
+        let array = vec![1i64].into_object();
+
+        assert_eq!(
+            i64::from_object(&Object::Boolean(true)),
+            Err(String::from("expected INTEGER, got BOOLEAN"))
+        );
+        assert_eq!(f64::from_object(&2.into_object()), Ok(2.0));
+        assert_eq!(
+            f64::from_object(&Object::Null),
+            Err(String::from("expected FLOAT, got NULL"))
+        );
+        assert_eq!(
+            String::from_object(&array),
+            Err(String::from("expected STRING, got ARRAY"))
+        );
+        assert_eq!(
+            Vec::<bool>::from_object(&array),
+            Err(String::from("expected BOOLEAN, got INTEGER"))
+        );
+        assert_eq!(
+            HashMap::<String, i64>::from_object(&Object::Null),
+            Err(String::from("expected HASH, got NULL"))
+        );
+    }
+
+    #[test]
+    fn host_functions() {
+        let add = |a: i64, b: i64| a + b;
+        let greet = |name: String| format!("Hello {}!", name);
+        let answer = || 42i64;
+        let checked = |a: i64| {
+            if a < 0 {
+                Err(String::from("negative"))
+            } else {
+                Ok(a)
+            }
+        };
+
+        let tests = vec![
+            (
+                HostFn::call(&add, "add", &[1.into_object(), 2.into_object()]),
+                "3",
+            ),
+            (
+                HostFn::call(&greet, "greet", &["Monkey".into_object()]),
+                "Hello Monkey!",
+            ),
+            (HostFn::call(&answer, "answer", &[]), "42"),
+            (
+                HostFn::call(&checked, "checked", &[(-1).into_object()]),
+                "ERROR: negative",
+            ),
+            (
+                HostFn::call(&add, "add", &[1.into_object()]),
+                "ERROR: wrong number of arguments: want=2, got=1",
+            ),
+            (
+                HostFn::call(&add, "add", &[1.into_object(), "2".into_object()]),
+                "ERROR: argument 2 to `add`: expected INTEGER, got STRING",
+            ),
+        ];
+
+        for (result, expected) in tests {
+            assert_eq!(result.inspect(), expected);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use crate::token::Span;
+use std::fmt;
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+// Additional location that helps to explain a diagnostic, e.g. where a paren was opened
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+// A problem found in the source code, reported by the lexer or the parser. Codes in use:
+//
+// E0001 unexpected character
+// E0002 unexpected token
+// E0003 expected an expression
+// E0004 invalid number literal
+// E0005 unterminated string
+// E0006 invalid escape sequence
+// E0007 unterminated block comment
+// E0008 expression nested too deeply
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,            // Primary location of the problem
+    pub label: Option<String>, // Printed next to the carets of the primary location
+    pub secondary: Vec<Label>,
+    pub help: Vec<String>,
+}
+
+// Whether the rendered diagnostic contains ANSI color codes
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum RenderMode {
+    Plain,
+    Color,
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: String, span: Span) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message,
+            span,
+            label: None,
+            secondary: vec![],
+            help: vec![],
+        }
+    }
+
+    pub fn with_label(mut self, message: String) -> Diagnostic {
+        self.label = Some(message);
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: String) -> Diagnostic {
+        self.secondary.push(Label { span, message });
+        self
+    }
+
+    pub fn with_help(mut self, message: String) -> Diagnostic {
+        self.help.push(message);
+        self
+    }
+
+    // Render the diagnostic with the offending source lines underlined, e.g.
+    //
+    // error[E0002]: expected `=`, got integer instead
+    //  --> 1:7
+    //   |
+    // 1 | let x 5;
+    //   |       ^ expected `=`
+    //   |
+    //   = help: a binding looks like `let <name> = <expression>;`
+    pub fn render(&self, source: &str, mode: RenderMode) -> String {
+        let paint = |color: &str, text: &str| match mode {
+            RenderMode::Plain => text.to_string(),
+            RenderMode::Color => format!("{}{}{}", color, text, RESET),
+        };
+        let severity_color = match self.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+
+        // Primary and secondary locations in source order, each gets its own underline row
+        let mut marks: Vec<(Span, char, &str, &str)> = vec![(
+            self.span,
+            '^',
+            self.label.as_deref().unwrap_or(""),
+            severity_color,
+        )];
+        for l in &self.secondary {
+            marks.push((l.span, '-', &l.message, BLUE));
+        }
+        marks.sort_by_key(|m| (m.0.line, m.0.column));
+
+        let max_line = marks.iter().map(|m| m.0.line).max().unwrap_or(1);
+        let width = max_line.to_string().len();
+        let gutter = paint(BLUE, &format!("{} |", " ".repeat(width)));
+
+        let mut out = format!(
+            "{}{}\n",
+            paint(severity_color, &format!("{}[{}]", self.severity, self.code)),
+            paint(BOLD, &format!(": {}", self.message))
+        );
+        out.push_str(&format!(
+            "{}{}\n",
+            paint(BLUE, &format!("{}--> ", " ".repeat(width))),
+            self.span
+        ));
+        out.push_str(&gutter);
+        out.push('\n');
+
+        let lines: Vec<&str> = source.lines().collect();
+        let mut last_line = 0;
+        for (span, marker, message, color) in marks {
+            let line = lines
+                .get(span.line.saturating_sub(1))
+                .copied()
+                .unwrap_or("");
+            let line = line.trim_end_matches('\r');
+
+            if span.line != last_line {
+                out.push_str(&paint(BLUE, &format!("{:>w$} |", span.line, w = width)));
+                out.push(' ');
+                out.push_str(line);
+                out.push('\n');
+                last_line = span.line;
+            }
+
+            // Keep tabs so the carets line up with the source above them
+            let before: String = line
+                .chars()
+                .take(span.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let underlined = line
+                .chars()
+                .skip(span.column.saturating_sub(1))
+                .scan(span.start, |offset, c| {
+                    let start = *offset;
+                    *offset += c.len_utf8();
+                    Some(start)
+                })
+                .take_while(|offset| *offset < span.end)
+                .count()
+                .max(1);
+
+            let mut underline = marker.to_string().repeat(underlined);
+            if !message.is_empty() {
+                underline.push(' ');
+                underline.push_str(message);
+            }
+            out.push_str(&format!(
+                "{} {}{}\n",
+                gutter,
+                before,
+                paint(color, &underline)
+            ));
+        }
+
+        if !self.help.is_empty() {
+            out.push_str(&gutter);
+            out.push('\n');
+            for h in &self.help {
+                out.push_str(&format!(
+                    "{} {} {}\n",
+                    paint(BLUE, &format!("{} =", " ".repeat(width))),
+                    paint(CYAN, "help:"),
+                    h
+                ));
+            }
+        }
+        out
+    }
+}
+
+// Single line form used in logs and test output, e.g. `1:7: error[E0002]: expected `=`...`
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}[{}]: {}",
+            self.span, self.severity, self.code, self.message
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn render_plain() {
+        let source = "let x = 1;\nlet y = (x + 2;\n";
+        let diagnostic = Diagnostic::error(
+            "E0002",
+            String::from("expected `)`, got `;` instead"),
+            span(25, 26, 2, 15),
+        )
+        .with_label(String::from("expected `)`"))
+        .with_secondary(span(19, 20, 2, 9), String::from("unclosed delimiter"))
+        .with_help(String::from("close the parenthesis"));
+
+        let expected = "error[E0002]: expected `)`, got `;` instead
+ --> 2:15
+  |
+2 | let y = (x + 2;
+  |         - unclosed delimiter
+  |               ^ expected `)`
+  |
+  = help: close the parenthesis
+";
+        assert_eq!(diagnostic.render(source, RenderMode::Plain), expected);
+    }
+
+    #[test]
+    fn render_multi_char_span_and_tabs() {
+        let source = "\tfoo == bar;";
+        let diagnostic = Diagnostic::error(
+            "E0003",
+            String::from("something is wrong"),
+            span(1, 4, 1, 2),
+        );
+
+        let expected = "error[E0003]: something is wrong
+ --> 1:2
+  |
+1 | \tfoo == bar;
+  | \t^^^
+";
+        assert_eq!(diagnostic.render(source, RenderMode::Plain), expected);
+    }
+
+    #[test]
+    fn render_color() {
+        let diagnostic = Diagnostic::error("E0001", String::from("bad"), span(0, 1, 1, 1));
+        let rendered = diagnostic.render("€", RenderMode::Color);
+
+        assert!(rendered.starts_with("\x1b[1;31merror[E0001]\x1b[0m"));
+        assert!(rendered.contains("\x1b[1;31m^\x1b[0m"));
+    }
+
+    #[test]
+    fn display() {
+        let diagnostic = Diagnostic::error("E0001", String::from("bad"), span(4, 5, 2, 3));
+        assert_eq!(diagnostic.to_string(), "2:3: error[E0001]: bad");
+    }
+}
@@ -0,0 +1,216 @@
+use crate::builtins::BUILTINS;
+use crate::lexer::is_identifier_continue;
+use crate::token::KEYWORDS;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::env;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+const HISTORY_FILE: &str = ".monkey_history";
+
+pub enum Input {
+    Line(String),
+    // Ctrl-C, drops whatever has been typed so far
+    Interrupted,
+    End,
+}
+
+// Where the REPL gets its lines from. `names` are the identifiers bound in the session,
+// offered for completion together with the keywords and builtins.
+pub trait LineReader {
+    fn read_line(&mut self, prompt: &str, names: &[String], output: &mut dyn Write) -> Input;
+}
+
+// Reads lines as they come, for input that is not a terminal
+pub struct PlainReader<R: BufRead> {
+    input: R,
+}
+
+impl<R: BufRead> PlainReader<R> {
+    pub fn new(input: R) -> PlainReader<R> {
+        PlainReader { input }
+    }
+}
+
+impl<R: BufRead> LineReader for PlainReader<R> {
+    fn read_line(&mut self, prompt: &str, _names: &[String], output: &mut dyn Write) -> Input {
+        write!(output, "{}", prompt).unwrap();
+        output.flush().unwrap();
+
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) | Err(_) => Input::End,
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                Input::Line(line)
+            }
+        }
+    }
+}
+
+// Readline style editing for terminals: arrow keys, Ctrl-R reverse search through the
+// history, tab completion. The history is kept in ~/.monkey_history between sessions.
+pub struct LineEditor {
+    editor: Editor<MonkeyHelper, DefaultHistory>,
+    history: Option<PathBuf>,
+}
+
+impl LineEditor {
+    pub fn new() -> rustyline::Result<LineEditor> {
+        let mut editor = Editor::new()?;
+        editor.set_helper(Some(MonkeyHelper { names: vec![] }));
+
+        let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        if let Some(path) = &history {
+            // There is no history file before the first session
+            let _ = editor.load_history(path);
+        }
+
+        Ok(LineEditor { editor, history })
+    }
+}
+
+impl LineReader for LineEditor {
+    fn read_line(&mut self, prompt: &str, names: &[String], _output: &mut dyn Write) -> Input {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.names = names.to_vec();
+        }
+
+        match self.editor.readline(prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = self.editor.add_history_entry(line.as_str());
+                }
+                Input::Line(line)
+            }
+            Err(ReadlineError::Interrupted) => Input::Interrupted,
+            Err(_) => Input::End,
+        }
+    }
+}
+
+impl Drop for LineEditor {
+    fn drop(&mut self) {
+        if let Some(path) = &self.history {
+            let _ = self.editor.save_history(path);
+        }
+    }
+}
+
+struct MonkeyHelper {
+    names: Vec<String>,
+}
+
+impl Completer for MonkeyHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(line, pos, &self.names))
+    }
+}
+
+impl Hinter for MonkeyHelper {
+    type Hint = String;
+}
+
+impl Highlighter for MonkeyHelper {}
+
+impl Validator for MonkeyHelper {}
+
+impl Helper for MonkeyHelper {}
+
+// The start of the word ending at `pos` and every keyword, builtin or name it is a prefix of
+fn complete(line: &str, pos: usize, names: &[String]) -> (usize, Vec<String>) {
+    let start = line[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_identifier_continue(*c))
+        .last()
+        .map_or(pos, |(i, _)| i);
+    let word = &line[start..pos];
+
+    let mut candidates: Vec<String> = KEYWORDS
+        .iter()
+        .map(|k| k.to_string())
+        .chain(BUILTINS.iter().map(|b| b.name.to_string()))
+        .chain(names.iter().cloned())
+        .filter(|c| c.starts_with(word))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+
+    (start, candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completion() {
+        let names = vec![
+            String::from("fib"),
+            String::from("result"),
+            String::from("prénom"),
+        ];
+
+        let tests = vec![
+            ("le", 2, 0, vec!["len", "let"]),
+            ("let x = f", 9, 8, vec!["false", "fib", "first", "fn"]),
+            ("pu", 2, 0, vec!["push", "puts"]),
+            ("fib(re", 6, 4, vec!["rest", "result", "return"]),
+            (
+                "add(",
+                4,
+                4,
+                vec![
+                    "else", "false", "fib", "first", "fn", "if", "last", "len", "let", "prénom",
+                    "push", "puts", "rest", "result", "return", "true",
+                ],
+            ),
+            ("le + 1", 2, 0, vec!["len", "let"]),
+            ("zz", 2, 0, vec![]),
+            ("1+pré", 6, 2, vec!["prénom"]),
+        ];
+
+        for (line, pos, start, expected) in tests {
+            let (got_start, got) = complete(line, pos, &names);
+            assert_eq!(got_start, start, "wrong start for {:?}", line);
+            assert_eq!(got, expected, "wrong candidates for {:?}", line);
+        }
+    }
+
+    #[test]
+    fn plain_reader() {
+        let mut reader = PlainReader::new("let x = 1;\r\nx\n".as_bytes());
+        let mut output = vec![];
+
+        for expected in &["let x = 1;", "x"] {
+            match reader.read_line(">>", &[], &mut output) {
+                Input::Line(line) => assert_eq!(&line, expected),
+                _ => panic!("expected a line"),
+            }
+        }
+        assert!(matches!(
+            reader.read_line(">>", &[], &mut output),
+            Input::End
+        ));
+        assert_eq!(output, b">>>>>>");
+    }
+}
@@ -0,0 +1,344 @@
+use crate::compiler::Compiler;
+use crate::convert::{FromObject, HostFn, IntoObject};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Lexer;
+use crate::object::*;
+use crate::parser::Parser;
+use crate::symbol_table::{SymbolScope, SymbolTable};
+use crate::vm::{GLOBALS_SIZE, VM};
+use std::fmt;
+use std::rc::Rc;
+
+// Monkey embedded in a Rust program, e.g. as a configuration or rules language. Programs are
+// compiled and run on the virtual machine against one set of globals, so bindings made by one
+// eval_str are visible to the next. The bytecode doesn't point into the source, any string
+// can be evaluated. `'a` is the lifetime of the registered Rust closures.
+//
+//     let mut engine = Engine::new();
+//     engine.register_fn("double", |x: i64| x * 2);
+//     engine.set_global("limit", 10)?;
+//     let result: i64 = engine.eval_str("double(limit) + 1")?;
+pub struct Engine<'a> {
+    symbol_table: SymbolTable,
+    constants: Vec<Object<'a>>,
+    globals: Vec<Option<Object<'a>>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Parse(Vec<Diagnostic>), // The program was not run
+    Compile(String),        // The program is too large for the virtual machine, it was not run
+    Runtime(String),
+    Conversion(String), // The value has a different type than the one asked for
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(diagnostics) => {
+                let lines: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            Error::Compile(msg) => write!(f, "compile error: {}", msg),
+            Error::Runtime(msg) => write!(f, "runtime error: {}", msg),
+            Error::Conversion(msg) => write!(f, "conversion error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl<'a> Engine<'a> {
+    pub fn new() -> Engine<'a> {
+        Engine {
+            symbol_table: SymbolTable::new(),
+            constants: vec![],
+            globals: vec![None; GLOBALS_SIZE],
+        }
+    }
+
+    // Run the program and convert the value of its last statement
+    pub fn eval_str<T: FromObject<'a>>(&mut self, source: &str) -> Result<T, Error> {
+        let l = Lexer::new(source);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        if !p.errors().is_empty() {
+            return Err(Error::Parse(p.errors().clone()));
+        }
+
+        let symbol_table = std::mem::take(&mut self.symbol_table);
+        let constants = std::mem::take(&mut self.constants);
+        let mut compiler = Compiler::new_with_state(symbol_table, constants);
+        let compiled = compiler.compile(&program);
+        let bytecode = compiler.bytecode();
+        let (symbol_table, constants) = compiler.into_state();
+        self.symbol_table = symbol_table;
+        self.constants = constants;
+        compiled.map_err(Error::Compile)?;
+
+        let mut vm = VM::new_with_globals(bytecode, std::mem::take(&mut self.globals));
+        let ran = vm.run();
+        let result = vm.last_popped_stack_elem();
+        self.globals = vm.into_globals();
+
+        ran.map_err(Error::Runtime)?;
+        T::from_object(&result).map_err(Error::Conversion)
+    }
+
+    // Fails when the value can't be turned into a Monkey value, e.g. a HashMap with keys
+    // that are not integers, booleans or strings
+    pub fn set_global<T: IntoObject<'a>>(&mut self, name: &str, value: T) -> Result<(), Error> {
+        match value.into_object() {
+            Object::Error(msg) => Err(Error::Conversion(msg)),
+            obj => {
+                self.define_global(name, obj);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn get_global<T: FromObject<'a>>(&self, name: &str) -> Result<T, Error> {
+        let global = self
+            .symbol_table
+            .symbols()
+            .into_iter()
+            .find(|s| s.name == name && s.scope == SymbolScope::Global)
+            .and_then(|s| self.globals[s.index].as_ref());
+        match global {
+            Some(obj) => T::from_object(obj).map_err(Error::Conversion),
+            None => Err(Error::Runtime(format!("identifier not found: {}", name))),
+        }
+    }
+
+    // Make a Rust closure callable from Monkey under the given name. The parameter types
+    // decide how arguments are converted, a call with the wrong ones is a runtime error.
+    pub fn register_fn<Args, F>(&mut self, name: &str, f: F)
+    where
+        F: HostFn<'a, Args> + 'a,
+    {
+        let fn_name = name.to_string();
+        let func = HostFunction {
+            name: name.to_string(),
+            func: Box::new(move |args: &[Object<'a>]| f.call(&fn_name, args)),
+        };
+        self.define_global(name, Object::HostFunction(Rc::new(func)));
+    }
+
+    fn define_global(&mut self, name: &str, obj: Object<'a>) {
+        let symbol = self.symbol_table.define(name);
+        self.globals[symbol.index] = Some(obj);
+    }
+}
+
+impl Default for Engine<'_> {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::collections::HashMap;
+
+    #[test]
+    fn eval_str() {
+        let mut engine = Engine::new();
+
+        assert_eq!(engine.eval_str::<i64>("1 + 2 * 3"), Ok(7));
+        assert_eq!(engine.eval_str::<bool>("1 < 2"), Ok(true));
+        assert_eq!(
+            engine.eval_str::<String>(r#""mon" + "key""#),
+            Ok(String::from("monkey"))
+        );
+        assert_eq!(
+            engine.eval_str::<Vec<i64>>("push([1, 2], 3)"),
+            Ok(vec![1, 2, 3])
+        );
+        assert_eq!(engine.eval_str::<Option<i64>>("if (false) { 1 }"), Ok(None));
+    }
+
+    #[test]
+    fn bindings_persist() {
+        let mut engine = Engine::new();
+
+        engine
+            .eval_str::<()>("let double = fn(x) { x * 2 }; let x = 21;")
+            .unwrap();
+        assert_eq!(engine.eval_str::<i64>("double(x)"), Ok(42));
+        assert_eq!(engine.get_global::<i64>("x"), Ok(21));
+    }
+
+    #[test]
+    fn globals() {
+        let mut engine = Engine::new();
+
+        let mut limits = HashMap::new();
+        limits.insert(String::from("daily"), 100i64);
+        limits.insert(String::from("monthly"), 2000);
+
+        engine.set_global("limits", limits.clone()).unwrap();
+        engine.set_global("user", "alice").unwrap();
+        engine.set_global("amounts", vec![30i64, 80]).unwrap();
+        engine.set_global("admin", false).unwrap();
+
+        let rules = r#"
+            let total = amounts[0] + amounts[1];
+            let allowed = if (admin) { true } else { total < limits["daily"] };
+            let message = user + ": " + if (allowed) { "ok" } else { "over limit" };
+        "#;
+        engine.eval_str::<()>(rules).unwrap();
+
+        assert_eq!(engine.get_global::<i64>("total"), Ok(110));
+        assert_eq!(engine.get_global::<bool>("allowed"), Ok(false));
+        assert_eq!(
+            engine.get_global::<String>("message"),
+            Ok(String::from("alice: over limit"))
+        );
+        assert_eq!(
+            engine.get_global::<HashMap<String, i64>>("limits"),
+            Ok(limits)
+        );
+        assert_eq!(
+            engine.get_global::<i64>("missing"),
+            Err(Error::Runtime(String::from(
+                "identifier not found: missing"
+            )))
+        );
+        assert_eq!(
+            engine.get_global::<i64>("user"),
+            Err(Error::Conversion(String::from(
+                "expected INTEGER, got STRING"
+            )))
+        );
+    }
+
+    #[test]
+    fn unconvertible_globals() {
+        let mut engine = Engine::new();
+
+        let mut by_pair = HashMap::new();
+        by_pair.insert(vec![1i64, 2], 3i64);
+        assert_eq!(
+            engine.set_global("by_pair", by_pair),
+            Err(Error::Conversion(String::from(
+                "unusable as hash key: ARRAY"
+            )))
+        );
+        assert_eq!(
+            engine.get_global::<i64>("by_pair"),
+            Err(Error::Runtime(String::from(
+                "identifier not found: by_pair"
+            )))
+        );
+    }
+
+    #[test]
+    fn sources_built_at_run_time() {
+        let mut engine = Engine::new();
+
+        for (i, name) in ["one", "two", "three"].iter().enumerate() {
+            let source = format!("let add_{} = fn(x) {{ x + {} }};", name, i + 1);
+            engine.eval_str::<()>(&source).unwrap();
+        }
+        assert_eq!(
+            engine.eval_str::<i64>("add_one(add_two(add_three(0)))"),
+            Ok(6)
+        );
+    }
+
+    #[test]
+    fn runaway_recursion() {
+        let mut engine = Engine::new();
+
+        assert_eq!(
+            engine.eval_str::<i64>("let f = fn(n) { f(n + 1) }; f(0)"),
+            Err(Error::Runtime(String::from("stack overflow")))
+        );
+        assert_eq!(
+            engine.eval_str::<i64>("f"),
+            Err(Error::Conversion(String::from(
+                "expected INTEGER, got FUNCTION"
+            )))
+        );
+    }
+
+    #[test]
+    fn host_functions() {
+        let calls = Cell::new(0);
+        let mut engine = Engine::new();
+
+        engine.register_fn("double", |x: i64| x * 2);
+        engine.register_fn("repeat", |s: String, n: i64| s.repeat(n as usize));
+        engine.register_fn("count", || {
+            calls.set(calls.get() + 1);
+            calls.get()
+        });
+        engine.register_fn("sqrt", |x: i64| {
+            if x < 0 {
+                return Err(String::from("square root of a negative number"));
+            }
+            Ok((x as f64).sqrt() as i64)
+        });
+
+        assert_eq!(engine.eval_str::<i64>("double(double(5))"), Ok(20));
+        assert_eq!(
+            engine.eval_str::<String>(r#"repeat("ab", 3)"#),
+            Ok(String::from("ababab"))
+        );
+        assert_eq!(engine.eval_str::<i64>("count(); count(); count()"), Ok(3));
+        assert_eq!(
+            engine.eval_str::<i64>("let twice = fn(f, x) { f(f(x)) }; twice(double, 3)"),
+            Ok(12)
+        );
+        assert_eq!(
+            engine.eval_str::<i64>("sqrt(-4)"),
+            Err(Error::Runtime(String::from(
+                "square root of a negative number"
+            )))
+        );
+        assert_eq!(
+            engine.eval_str::<i64>(r#"double("2")"#),
+            Err(Error::Runtime(String::from(
+                "argument 1 to `double`: expected INTEGER, got STRING"
+            )))
+        );
+        assert_eq!(
+            engine.eval_str::<i64>("double(1, 2)"),
+            Err(Error::Runtime(String::from(
+                "wrong number of arguments: want=1, got=2"
+            )))
+        );
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn errors() {
+        let mut engine = Engine::new();
+
+        match engine.eval_str::<i64>("let x 5;") {
+            Err(Error::Parse(diagnostics)) => {
+                assert_eq!(diagnostics.len(), 1);
+                assert_eq!(diagnostics[0].code, "E0002");
+            }
+            result => panic!("expected a parse error. got={:?}", result),
+        }
+        assert_eq!(
+            engine.eval_str::<i64>("5 + true"),
+            Err(Error::Runtime(String::from(
+                "type mismatch: INTEGER + BOOLEAN"
+            )))
+        );
+        assert_eq!(
+            engine.eval_str::<bool>("5"),
+            Err(Error::Conversion(String::from(
+                "expected BOOLEAN, got INTEGER"
+            )))
+        );
+        assert_eq!(
+            Error::Runtime(String::from("division by zero")).to_string(),
+            "runtime error: division by zero"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use crate::object::Object;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Bindings created by `let` statements and function parameters. Function calls get a new
+// environment enclosed by the one the function was defined in, which gives closures.
+#[derive(Debug, Default)]
+pub struct Environment<'a> {
+    store: HashMap<String, Object<'a>>,
+    outer: Option<Rc<RefCell<Environment<'a>>>>,
+}
+
+impl<'a> Environment<'a> {
+    pub fn new() -> Rc<RefCell<Environment<'a>>> {
+        Rc::new(RefCell::new(Environment::default()))
+    }
+
+    pub fn new_enclosed(outer: Rc<RefCell<Environment<'a>>>) -> Rc<RefCell<Environment<'a>>> {
+        Rc::new(RefCell::new(Environment {
+            store: HashMap::new(),
+            outer: Some(outer),
+        }))
+    }
+
+    // Lookup the name in this scope first and then in the enclosing ones
+    pub fn get(&self, name: &str) -> Option<Object<'a>> {
+        match self.store.get(name) {
+            Some(obj) => Some(obj.clone()),
+            None => match &self.outer {
+                Some(outer) => outer.borrow().get(name),
+                None => None,
+            },
+        }
+    }
+
+    // The bindings of this scope (not the enclosing ones), sorted by name
+    pub fn bindings(&self) -> Vec<(&String, &Object<'a>)> {
+        let mut bindings: Vec<(&String, &Object<'a>)> = self.store.iter().collect();
+        bindings.sort_by(|a, b| a.0.cmp(b.0));
+        bindings
+    }
+
+    pub fn set(&mut self, name: String, val: Object<'a>) -> Object<'a> {
+        self.store.insert(name, val.clone());
+        val
+    }
+}
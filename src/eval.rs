@@ -0,0 +1,960 @@
+use crate::ast::*;
+use crate::builtins::{self, BUILTINS};
+use crate::environment::Environment;
+use crate::integer::{self, Integer};
+use crate::object::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+type Env<'a> = Rc<RefCell<Environment<'a>>>;
+
+// Calls that can be nested. Each one takes a few kilobytes of native stack, this keeps a
+// runaway recursion inside the 2 MiB stack of a thread in a release build and inside the
+// 8 MiB of the main thread in a debug build.
+const MAX_CALL_DEPTH: usize = 512;
+
+pub fn eval_program<'a>(program: &Program<'a>, env: &Env<'a>) -> Object<'a> {
+    let mut result = Object::Null;
+
+    for statement in &program.statements {
+        result = eval_statement(statement, env, 0);
+
+        // A top level `return` stops the program and hands back the bare value
+        match result {
+            Object::ReturnValue(value) => return *value,
+            Object::Error(_) => return result,
+            _ => (),
+        }
+    }
+    result
+}
+
+// Unlike eval_program the return value is not unwrapped here, so that a `return` inside a
+// nested block stops the execution of the outer blocks as well
+fn eval_block_statement<'a>(block: &BlockStatement<'a>, env: &Env<'a>, depth: usize) -> Object<'a> {
+    let mut result = Object::Null;
+
+    for statement in &block.statements {
+        result = eval_statement(statement, env, depth);
+
+        match result {
+            Object::ReturnValue(_) | Object::Error(_) => return result,
+            _ => (),
+        }
+    }
+    result
+}
+
+fn eval_statement<'a>(statement: &Statement<'a>, env: &Env<'a>, depth: usize) -> Object<'a> {
+    match statement {
+        Statement::ExpressionStatement(x) => eval_expression(&x.expression, env, depth),
+        Statement::ReturnStatement(x) => {
+            let val = eval_expression(&x.return_value, env, depth);
+            if val.is_error() {
+                return val;
+            }
+            Object::ReturnValue(Box::new(val))
+        }
+        Statement::LetStatement(x) => {
+            let val = eval_expression(&x.value, env, depth);
+            if val.is_error() {
+                return val;
+            }
+            env.borrow_mut().set(x.name.value.clone(), val);
+            Object::Null
+        }
+    }
+}
+
+fn eval_expression<'a>(expression: &Expression<'a>, env: &Env<'a>, depth: usize) -> Object<'a> {
+    match expression {
+        Expression::IntegerLiteral(x) => Object::Integer(x.value.to_owned()),
+        Expression::FloatLiteral(x) => Object::Float(x.value),
+        Expression::StringLiteral(x) => Object::String(x.value.clone()),
+        Expression::Boolean(x) => Object::Boolean(x.value),
+        Expression::Identifier(x) => eval_identifier(x, env),
+        Expression::PrefixExpression(x) => {
+            let right = eval_expression(&x.right, env, depth);
+            if right.is_error() {
+                return right;
+            }
+            eval_prefix_expression(&x.operator, right)
+        }
+        Expression::InfixExpression(x) => {
+            let left = eval_expression(&x.left, env, depth);
+            if left.is_error() {
+                return left;
+            }
+            let right = eval_expression(&x.right, env, depth);
+            if right.is_error() {
+                return right;
+            }
+            eval_infix_expression(&x.operator, left, right)
+        }
+        Expression::IfExpression(x) => eval_if_expression(x, env, depth),
+        Expression::FunctionLiteral(x) => Object::Function(Rc::new(Function {
+            parameters: x.parameters.clone(),
+            body: x.body.clone(),
+            env: Rc::clone(env),
+        })),
+        Expression::CallExpression(x) => {
+            let function = eval_expression(&x.function, env, depth);
+            if function.is_error() {
+                return function;
+            }
+            let args = match eval_expressions(&x.arguments, env, depth) {
+                Ok(args) => args,
+                Err(err) => return err,
+            };
+            apply_function(function, args, depth)
+        }
+        Expression::ArrayLiteral(x) => match eval_expressions(&x.elements, env, depth) {
+            Ok(elements) => Object::Array(Rc::new(elements)),
+            Err(err) => err,
+        },
+        Expression::HashLiteral(x) => eval_hash_literal(x, env, depth),
+        Expression::IndexExpression(x) => {
+            let left = eval_expression(&x.left, env, depth);
+            if left.is_error() {
+                return left;
+            }
+            let index = eval_expression(&x.index, env, depth);
+            if index.is_error() {
+                return index;
+            }
+            eval_index_expression(left, index)
+        }
+    }
+}
+
+fn eval_hash_literal<'a>(node: &HashLiteral<'a>, env: &Env<'a>, depth: usize) -> Object<'a> {
+    let mut pairs = HashMap::new();
+
+    for (key_node, value_node) in &node.pairs {
+        let key = eval_expression(key_node, env, depth);
+        if key.is_error() {
+            return key;
+        }
+        let hash_key = match key.hash_key() {
+            Some(hash_key) => hash_key,
+            None => return unusable_as_hash_key(&key),
+        };
+
+        let value = eval_expression(value_node, env, depth);
+        if value.is_error() {
+            return value;
+        }
+        pairs.insert(hash_key, HashPair { key, value });
+    }
+    Object::Hash(Rc::new(pairs))
+}
+
+// Build a hash from alternating keys and values, later keys replace earlier equal ones
+pub fn build_hash<'a>(keys_and_values: &[Object<'a>]) -> Object<'a> {
+    let mut pairs = HashMap::new();
+
+    for pair in keys_and_values.chunks(2) {
+        let (key, value) = (pair[0].clone(), pair[1].clone());
+        match key.hash_key() {
+            Some(hash_key) => pairs.insert(hash_key, HashPair { key, value }),
+            None => return unusable_as_hash_key(&key),
+        };
+    }
+    Object::Hash(Rc::new(pairs))
+}
+
+fn unusable_as_hash_key<'a>(key: &Object) -> Object<'a> {
+    new_error(format!("unusable as hash key: {}", key.object_type()))
+}
+
+// Indexing past either end of an array or with a missing key gives null
+pub fn eval_index_expression<'a>(left: Object<'a>, index: Object<'a>) -> Object<'a> {
+    match (&left, &index) {
+        (Object::Array(elements), Object::Integer(i)) => match integer::to_usize(i) {
+            Some(i) if i < elements.len() => elements[i].clone(),
+            _ => Object::Null,
+        },
+        (Object::Hash(pairs), _) => match index.hash_key() {
+            Some(hash_key) => match pairs.get(&hash_key) {
+                Some(pair) => pair.value.clone(),
+                None => Object::Null,
+            },
+            None => unusable_as_hash_key(&index),
+        },
+        _ => new_error(format!(
+            "index operator not supported: {}[{}]",
+            left.object_type(),
+            index.object_type()
+        )),
+    }
+}
+
+// An if without an else whose condition is falsy evaluates to null
+fn eval_if_expression<'a>(ie: &IfExpression<'a>, env: &Env<'a>, depth: usize) -> Object<'a> {
+    let condition = eval_expression(&ie.condition, env, depth);
+    if condition.is_error() {
+        return condition;
+    }
+
+    if is_truthy(&condition) {
+        eval_block_statement(&ie.consequence, env, depth)
+    } else {
+        match &ie.alternative {
+            Some(alternative) => eval_block_statement(alternative, env, depth),
+            None => Object::Null,
+        }
+    }
+}
+
+// Evaluate the expressions left to right, stopping at the first error
+fn eval_expressions<'a>(
+    expressions: &[Expression<'a>],
+    env: &Env<'a>,
+    depth: usize,
+) -> Result<Vec<Object<'a>>, Object<'a>> {
+    let mut result = vec![];
+
+    for e in expressions {
+        let evaluated = eval_expression(e, env, depth);
+        if evaluated.is_error() {
+            return Err(evaluated);
+        }
+        result.push(evaluated);
+    }
+    Ok(result)
+}
+
+fn eval_identifier<'a>(node: &Identifier<'a>, env: &Env<'a>) -> Object<'a> {
+    match env.borrow().get(&node.value) {
+        Some(val) => val,
+        None => match builtins::lookup(&node.value) {
+            Some(index) => Object::Builtin(BUILTINS[index]),
+            None => new_error(format!("identifier not found: {}", node.value)),
+        },
+    }
+}
+
+pub fn eval_prefix_expression<'a>(operator: &str, right: Object<'a>) -> Object<'a> {
+    match operator {
+        "!" => eval_bang_operator_expression(right),
+        "-" => eval_minus_prefix_operator_expression(right),
+        _ => new_error(format!(
+            "unknown operator: {}{}",
+            operator,
+            right.object_type()
+        )),
+    }
+}
+
+fn eval_bang_operator_expression<'a>(right: Object<'a>) -> Object<'a> {
+    Object::Boolean(!is_truthy(&right))
+}
+
+fn eval_minus_prefix_operator_expression<'a>(right: Object<'a>) -> Object<'a> {
+    match right {
+        Object::Integer(value) => match integer::neg(&value) {
+            Ok(value) => Object::Integer(value),
+            Err(msg) => new_error(msg),
+        },
+        Object::Float(value) => Object::Float(-value),
+        _ => new_error(format!("unknown operator: -{}", right.object_type())),
+    }
+}
+
+pub fn eval_infix_expression<'a>(
+    operator: &str,
+    left: Object<'a>,
+    right: Object<'a>,
+) -> Object<'a> {
+    match (&left, &right) {
+        (Object::Integer(l), Object::Integer(r)) => eval_integer_infix_expression(operator, l, r),
+        (Object::Float(l), Object::Float(r)) => eval_float_infix_expression(operator, *l, *r),
+        (Object::Integer(l), Object::Float(r)) => {
+            eval_float_infix_expression(operator, integer::to_f64(l), *r)
+        }
+        (Object::Float(l), Object::Integer(r)) => {
+            eval_float_infix_expression(operator, *l, integer::to_f64(r))
+        }
+        (Object::String(l), Object::String(r)) => eval_string_infix_expression(operator, l, r),
+        (Object::Boolean(l), Object::Boolean(r)) => match operator {
+            "==" => Object::Boolean(l == r),
+            "!=" => Object::Boolean(l != r),
+            _ => new_error(format!(
+                "unknown operator: {} {} {}",
+                left.object_type(),
+                operator,
+                right.object_type()
+            )),
+        },
+        _ if left.object_type() != right.object_type() => new_error(format!(
+            "type mismatch: {} {} {}",
+            left.object_type(),
+            operator,
+            right.object_type()
+        )),
+        _ => new_error(format!(
+            "unknown operator: {} {} {}",
+            left.object_type(),
+            operator,
+            right.object_type()
+        )),
+    }
+}
+
+// Arithmetic on two integers stays integer arithmetic, as soon as one side is a float the
+// integer is converted and the result is a float: 1 + 2 is 3 but 1 + 2.0 is 3.0. That makes
+// `/` integer division for two integers, truncating towards zero (7 / 2 is 3, -7 / 2 is -3),
+// while 7 / 2.0 is 3.5. Dividing by zero is an error for integers and floats alike, so there
+// is no way to get infinity or NaN out of a division. Integer arithmetic that overflows is an
+// error too, see the integer module.
+fn eval_integer_infix_expression<'a>(
+    operator: &str,
+    left: &Integer,
+    right: &Integer,
+) -> Object<'a> {
+    let result = match operator {
+        "+" => integer::add(left, right),
+        "-" => integer::sub(left, right),
+        "*" => integer::mul(left, right),
+        "/" => integer::div(left, right),
+        _ => return eval_integer_comparison(operator, left, right),
+    };
+    match result {
+        Ok(value) => Object::Integer(value),
+        Err(msg) => new_error(msg),
+    }
+}
+
+fn eval_integer_comparison<'a>(operator: &str, left: &Integer, right: &Integer) -> Object<'a> {
+    match operator {
+        "<" => Object::Boolean(left < right),
+        ">" => Object::Boolean(left > right),
+        "==" => Object::Boolean(left == right),
+        "!=" => Object::Boolean(left != right),
+        _ => new_error(format!("unknown operator: INTEGER {} INTEGER", operator)),
+    }
+}
+
+fn eval_float_infix_expression<'a>(operator: &str, left: f64, right: f64) -> Object<'a> {
+    match operator {
+        "+" => Object::Float(left + right),
+        "-" => Object::Float(left - right),
+        "*" => Object::Float(left * right),
+        "/" => {
+            if right == 0.0 {
+                return new_error(String::from("division by zero"));
+            }
+            Object::Float(left / right)
+        }
+        "<" => Object::Boolean(left < right),
+        ">" => Object::Boolean(left > right),
+        "==" => Object::Boolean(left == right),
+        "!=" => Object::Boolean(left != right),
+        _ => new_error(format!("unknown operator: FLOAT {} FLOAT", operator)),
+    }
+}
+
+fn eval_string_infix_expression<'a>(operator: &str, left: &str, right: &str) -> Object<'a> {
+    match operator {
+        "+" => Object::String(format!("{}{}", left, right)),
+        "==" => Object::Boolean(left == right),
+        "!=" => Object::Boolean(left != right),
+        _ => new_error(format!("unknown operator: STRING {} STRING", operator)),
+    }
+}
+
+// `depth` is the number of calls in progress. Each one takes native stack, so runaway
+// recursion has to end in an error before the stack of the interpreter runs out.
+fn apply_function<'a>(function: Object<'a>, args: Vec<Object<'a>>, depth: usize) -> Object<'a> {
+    match function {
+        Object::Function(function) => {
+            if function.parameters.len() != args.len() {
+                return new_error(format!(
+                    "wrong number of arguments: want={}, got={}",
+                    function.parameters.len(),
+                    args.len()
+                ));
+            }
+            if depth >= MAX_CALL_DEPTH {
+                return new_error(String::from("stack overflow"));
+            }
+            let extended_env = extend_function_env(&function, args);
+            let evaluated = eval_block_statement(&function.body, &extended_env, depth + 1);
+            unwrap_return_value(evaluated)
+        }
+        Object::Builtin(builtin) => (builtin.func)(&args),
+        Object::HostFunction(host) => (host.func)(&args),
+        _ => new_error(format!("not a function: {}", function.object_type())),
+    }
+}
+
+// Bind the arguments to the parameter names in a new scope enclosed by the function's own
+fn extend_function_env<'a>(function: &Function<'a>, args: Vec<Object<'a>>) -> Env<'a> {
+    let env = Environment::new_enclosed(Rc::clone(&function.env));
+
+    for (param, arg) in function.parameters.iter().zip(args) {
+        env.borrow_mut().set(param.value.clone(), arg);
+    }
+    env
+}
+
+// A `return` must only leave the function it is in, not every caller up the stack
+fn unwrap_return_value(obj: Object) -> Object {
+    match obj {
+        Object::ReturnValue(value) => *value,
+        _ => obj,
+    }
+}
+
+// Only `false` and `null` are falsy, everything else is truthy
+pub fn is_truthy(obj: &Object) -> bool {
+    match obj {
+        Object::Null => false,
+        Object::Boolean(value) => *value,
+        _ => true,
+    }
+}
+
+fn new_error<'a>(msg: String) -> Object<'a> {
+    Object::Error(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn test_eval(input: &str) -> Object<'_> {
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+        let env = Environment::new();
+
+        eval_program(&program, &env)
+    }
+
+    fn test_integer_object(obj: &Object, expected: i64) {
+        match obj {
+            Object::Integer(x) => assert_eq!(
+                *x,
+                integer::from_i64(expected),
+                "object has wrong value. got={}, want={}",
+                x,
+                expected
+            ),
+            _ => panic!("object is not Integer. got={:?}", obj),
+        }
+    }
+
+    fn test_boolean_object(obj: &Object, expected: bool) {
+        match obj {
+            Object::Boolean(x) => assert_eq!(
+                *x, expected,
+                "object has wrong value. got={}, want={}",
+                x, expected
+            ),
+            _ => panic!("object is not Boolean. got={:?}", obj),
+        }
+    }
+
+    #[test]
+    fn eval_integer_expression() {
+        let tests = vec![
+            ("5", 5),
+            ("10", 10),
+            ("-5", -5),
+            ("-10", -10),
+            ("5 + 5 + 5 + 5 - 10", 10),
+            ("2 * 2 * 2 * 2 * 2", 32),
+            ("-50 + 100 + -50", 0),
+            ("5 * 2 + 10", 20),
+            ("5 + 2 * 10", 25),
+            ("20 + 2 * -10", 0),
+            ("50 / 2 * 2 + 10", 60),
+            ("2 * (5 + 10)", 30),
+            ("3 * 3 * 3 + 10", 37),
+            ("3 * (3 * 3) + 10", 37),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+            ("0xff + 0o17 + 0b11", 273),
+            ("1_000_000 / 1_000", 1000),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(&test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn eval_float_expression() {
+        let tests = vec![
+            ("1.5", "1.5"),
+            ("-2.5", "-2.5"),
+            ("2.0", "2.0"),
+            ("1e3", "1000.0"),
+            ("1.5 + 1.5", "3.0"),
+            ("0.1 * 3", "0.30000000000000004"),
+            ("1 + 0.5", "1.5"),
+            ("0.5 + 1", "1.5"),
+            ("10 - 2.5 * 2", "5.0"),
+            ("-(1 - 3.5)", "2.5"),
+            ("1.5 < 2", "true"),
+            ("2 > 1.5", "true"),
+            ("1 == 1.0", "true"),
+            ("0.1 + 0.2 == 0.3", "false"),
+            ("1.5 != 1.5", "false"),
+            ("!0.0", "false"),
+            ("[1.5, 2][0]", "1.5"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input).inspect(), expected, "input: {}", input);
+        }
+    }
+
+    // See eval_integer_infix_expression for the rules
+    #[test]
+    fn division() {
+        let tests = vec![
+            ("7 / 2", "3"),
+            ("-7 / 2", "-3"),
+            ("7 / -2", "-3"),
+            ("6 / 3", "2"),
+            ("7 / 2.0", "3.5"),
+            ("7.0 / 2", "3.5"),
+            ("-7.5 / 2.5", "-3.0"),
+            ("1 / 3.0", "0.3333333333333333"),
+            ("1 / 0", "ERROR: division by zero"),
+            ("1.5 / 0", "ERROR: division by zero"),
+            ("1 / 0.0", "ERROR: division by zero"),
+            ("0.0 / 0.0", "ERROR: division by zero"),
+            ("1.0 / -0.0", "ERROR: division by zero"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input).inspect(), expected, "input: {}", input);
+        }
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn integer_overflow() {
+        let tests = vec![
+            "9223372036854775807 + 1",
+            "-9223372036854775807 - 2",
+            "4611686018427387904 * 2",
+            "let min = -9223372036854775807 - 1; -min",
+            "let min = -9223372036854775807 - 1; min / -1",
+        ];
+
+        for input in tests {
+            assert_eq!(
+                test_eval(input).inspect(),
+                "ERROR: integer overflow",
+                "input: {}",
+                input
+            );
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn big_integers() {
+        let tests = vec![
+            ("9223372036854775807 + 1", "9223372036854775808"),
+            (
+                "123456789012345678901234567890 * 10",
+                "1234567890123456789012345678900",
+            ),
+            ("-(-9223372036854775807 - 1)", "9223372036854775808"),
+            ("100000000000000000000 / 3", "33333333333333333333"),
+            ("0xffff_ffff_ffff_ffff_ffff", "1208925819614629174706175"),
+            ("100000000000000000000 > 99999999999999999999", "true"),
+            ("100000000000000000000 + 0.5", "1e20"),
+            ("[1, 2][100000000000000000000]", "null"),
+            ("{100000000000000000000: 1}[100000000000000000000]", "1"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input).inspect(), expected, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn eval_boolean_expression() {
+        let tests = vec![
+            ("true", true),
+            ("false", false),
+            ("1 < 2", true),
+            ("1 > 2", false),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("1 != 2", true),
+            ("true == true", true),
+            ("true != false", true),
+            ("(1 < 2) == true", true),
+            ("(1 > 2) == true", false),
+            ("!true", false),
+            ("!5", false),
+            ("!!true", true),
+            ("!!5", true),
+        ];
+
+        for (input, expected) in tests {
+            test_boolean_object(&test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn if_else_expressions() {
+        let tests = vec![
+            ("if (true) { 10 }", Some(10)),
+            ("if (false) { 10 }", None),
+            ("if (1) { 10 }", Some(10)),
+            ("if (1 < 2) { 10 }", Some(10)),
+            ("if (1 > 2) { 10 }", None),
+            ("if (1 > 2) { 10 } else { 20 }", Some(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Some(10)),
+        ];
+
+        for (input, expected) in tests {
+            let evaluated = test_eval(input);
+            match expected {
+                Some(expected) => test_integer_object(&evaluated, expected),
+                None => match evaluated {
+                    Object::Null => (),
+                    obj => panic!("object is not NULL. got={:?}", obj),
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn return_statements() {
+        let tests = vec![
+            ("return 10;", 10),
+            ("return 10; 9;", 10),
+            ("return 2 * 5; 9;", 10),
+            ("9; return 2 * 5; 9;", 10),
+            ("let f = fn() { return 10; 9; }; f();", 10),
+            ("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", 10),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(&test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn error_handling() {
+        let tests = vec![
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("5; true + false; 5", "unknown operator: BOOLEAN + BOOLEAN"),
+            (
+                "if (10 > 1) { true + false; }",
+                "unknown operator: BOOLEAN + BOOLEAN",
+            ),
+            ("foobar", "identifier not found: foobar"),
+            (r#""Hello" - "World""#, "unknown operator: STRING - STRING"),
+            (r#""Hello" + 1"#, "type mismatch: STRING + INTEGER"),
+            ("1[0]", "index operator not supported: INTEGER[INTEGER]"),
+            ("[1][true]", "index operator not supported: ARRAY[BOOLEAN]"),
+            ("[1, foo]", "identifier not found: foo"),
+            (
+                r#"{"name": "Monkey"}[fn(x) { x }];"#,
+                "unusable as hash key: FUNCTION",
+            ),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+            ("{{}: 2}", "unusable as hash key: HASH"),
+            ("{1.5: 2}", "unusable as hash key: FLOAT"),
+            ("1.5 + true", "type mismatch: FLOAT + BOOLEAN"),
+            (r#""a" + 1.5"#, "type mismatch: STRING + FLOAT"),
+            ("-[1.5]", "unknown operator: -ARRAY"),
+            ("{1: foo}", "identifier not found: foo"),
+            ("10 / 0", "division by zero"),
+            ("let x = 5; x(1)", "not a function: INTEGER"),
+            (
+                "fn(x) { x }(1, 2)",
+                "wrong number of arguments: want=1, got=2",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Object::Error(msg) => assert_eq!(
+                    msg, expected,
+                    "wrong error message. expected={}, got={}",
+                    expected, msg
+                ),
+                obj => panic!("no error object returned. got={:?}", obj),
+            }
+        }
+    }
+
+    #[test]
+    fn string_literal() {
+        match test_eval(r#""Hello\tWorld!""#) {
+            Object::String(x) => assert_eq!(x, "Hello\tWorld!"),
+            obj => panic!("object is not String. got={:?}", obj),
+        }
+    }
+
+    #[test]
+    fn string_concatenation() {
+        match test_eval(r#"let greet = fn(name) { "Hello" + " " + name + "!" }; greet("été")"#) {
+            Object::String(x) => assert_eq!(x, "Hello été!"),
+            obj => panic!("object is not String. got={:?}", obj),
+        }
+    }
+
+    #[test]
+    fn string_comparison() {
+        let tests = vec![
+            (r#""a" == "a""#, true),
+            (r#""a" == "b""#, false),
+            (r#""a" != "b""#, true),
+            (r#""a" + "b" == "ab""#, true),
+            (r#""" != "a""#, true),
+        ];
+
+        for (input, expected) in tests {
+            test_boolean_object(&test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn array_literals() {
+        match test_eval("[1, 2 * 2, 3 + 3]") {
+            Object::Array(elements) => {
+                assert_eq!(
+                    elements.len(),
+                    3,
+                    "array has wrong num of elements. got={}",
+                    elements.len()
+                );
+                test_integer_object(&elements[0], 1);
+                test_integer_object(&elements[1], 4);
+                test_integer_object(&elements[2], 6);
+            }
+            obj => panic!("object is not Array. got={:?}", obj),
+        }
+    }
+
+    #[test]
+    fn array_index_expressions() {
+        let tests = vec![
+            ("[1, 2, 3][0]", Some(1)),
+            ("[1, 2, 3][1]", Some(2)),
+            ("[1, 2, 3][2]", Some(3)),
+            ("let i = 0; [1][i];", Some(1)),
+            ("[1, 2, 3][1 + 1];", Some(3)),
+            ("let myArray = [1, 2, 3]; myArray[2];", Some(3)),
+            (
+                "let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];",
+                Some(6),
+            ),
+            (
+                "let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]",
+                Some(2),
+            ),
+            ("[1, 2, 3][3]", None),
+            ("[1, 2, 3][-1]", None),
+            ("[][0]", None),
+            ("[[1, 2], [3]][0][1]", Some(2)),
+            ("[1, fn(x) { x * 3 }][1](2)", Some(6)),
+        ];
+
+        for (input, expected) in tests {
+            let evaluated = test_eval(input);
+            match expected {
+                Some(expected) => test_integer_object(&evaluated, expected),
+                None => assert!(
+                    matches!(evaluated, Object::Null),
+                    "object is not NULL. got={:?}",
+                    evaluated
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn hash_literals() {
+        let input = r#"let two = "two";
+        {
+            "one": 10 - 9,
+            two: 1 + 1,
+            "thr" + "ee": 6 / 2,
+            4: 4,
+            true: 5,
+            false: 6
+        }"#;
+
+        let pairs = match test_eval(input) {
+            Object::Hash(pairs) => pairs,
+            obj => panic!("Eval didn't return Hash. got={:?}", obj),
+        };
+
+        let expected = vec![
+            (HashKey::String(String::from("one")), 1),
+            (HashKey::String(String::from("two")), 2),
+            (HashKey::String(String::from("three")), 3),
+            (HashKey::Integer(integer::from_i64(4)), 4),
+            (HashKey::Boolean(true), 5),
+            (HashKey::Boolean(false), 6),
+        ];
+        assert_eq!(
+            pairs.len(),
+            expected.len(),
+            "Hash has wrong num of pairs. got={}",
+            pairs.len()
+        );
+        for (key, value) in expected {
+            match pairs.get(&key) {
+                Some(pair) => test_integer_object(&pair.value, value),
+                None => panic!("no pair for given key in Pairs: {:?}", key),
+            }
+        }
+    }
+
+    #[test]
+    fn hash_index_expressions() {
+        let tests = vec![
+            (r#"{"foo": 5}["foo"]"#, Some(5)),
+            (r#"{"foo": 5}["bar"]"#, None),
+            (r#"let key = "foo"; {"foo": 5}[key]"#, Some(5)),
+            (r#"{}["foo"]"#, None),
+            ("{5: 5}[5]", Some(5)),
+            ("{true: 5}[true]", Some(5)),
+            ("{false: 5}[false]", Some(5)),
+            ("{1: 1, 1: 2}[1]", Some(2)),
+        ];
+
+        for (input, expected) in tests {
+            let evaluated = test_eval(input);
+            match expected {
+                Some(expected) => test_integer_object(&evaluated, expected),
+                None => assert!(
+                    matches!(evaluated, Object::Null),
+                    "object is not NULL. got={:?}",
+                    evaluated
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn builtin_functions() {
+        let tests = vec![
+            (r#"len("")"#, "0"),
+            (r#"len("four")"#, "4"),
+            (r#"len("hello" + " world")"#, "11"),
+            ("len([1, 2, 3])", "3"),
+            (r#"len({"a": 1})"#, "1"),
+            ("first([1, 2, 3])", "1"),
+            ("last([1, 2, 3])", "3"),
+            ("rest([1, 2, 3])", "[2, 3]"),
+            ("push([1, 2], 3)", "[1, 2, 3]"),
+            ("let a = [1]; push(a, 2); a", "[1]"),
+            (r#"puts("hello", 1)"#, "null"),
+            ("len(1)", "ERROR: argument to `len` not supported, got INTEGER"),
+            (
+                r#"len("one", "two")"#,
+                "ERROR: wrong number of arguments: want=1, got=2",
+            ),
+            ("first(1)", "ERROR: argument to `first` must be ARRAY, got INTEGER"),
+            ("len", "builtin function"),
+            ("let len = fn(x) { 42 }; len([1])", "42"),
+            (
+                "let map = fn(arr, f) { let iter = fn(arr, acc) { if (len(arr) == 0) { acc } else { iter(rest(arr), push(acc, f(first(arr)))) } }; iter(arr, []) }; map([1, 2, 3], fn(x) { x * 2 })",
+                "[2, 4, 6]",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input).inspect(), expected, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn let_statements() {
+        let tests = vec![
+            ("let a = 5; a;", 5),
+            ("let a = 5 * 5; a;", 25),
+            ("let a = 5; let b = a; b;", 5),
+            ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(&test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn function_object() {
+        match test_eval("fn(x) { x + 2; };") {
+            Object::Function(f) => {
+                assert_eq!(f.parameters.len(), 1);
+                assert_eq!(f.parameters[0].string(), "x");
+                assert_eq!(f.body.string(), "(x + 2)");
+            }
+            obj => panic!("object is not Function. got={:?}", obj),
+        }
+    }
+
+    #[test]
+    fn function_application() {
+        let tests = vec![
+            ("let identity = fn(x) { x; }; identity(5);", 5),
+            ("let identity = fn(x) { return x; }; identity(5);", 5),
+            ("let double = fn(x) { x * 2; }; double(5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
+            ("let add = fn(x, y) { x + y }; add(5, 10)", 15),
+            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
+            ("fn(x) { x; }(5)", 5),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(&test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn closures() {
+        let input = "
+        let newAdder = fn(x) {
+            fn(y) { x + y };
+        };
+
+        let addTwo = newAdder(2);
+        addTwo(2);";
+
+        test_integer_object(&test_eval(input), 4);
+    }
+
+    #[test]
+    fn call_depth() {
+        // Test threads only get 2 MiB, debug builds need more for the deepest recursion
+        let deepest = std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(|| {
+                let countdown = "let c = fn(n) { if (n == 0) { 0 } else { c(n - 1) } };";
+                [500, 512, 5000]
+                    .iter()
+                    .map(|n| test_eval(&format!("{} c({})", countdown, n)).inspect())
+                    .collect::<Vec<String>>()
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(
+            deepest,
+            vec!["0", "ERROR: stack overflow", "ERROR: stack overflow"]
+        );
+    }
+}
@@ -0,0 +1,190 @@
+// The integer type of Monkey programs. By default it is a 64-bit integer whose arithmetic
+// reports overflow as a runtime error. Building with the `bigint` feature makes it an
+// arbitrary-precision integer instead, which never overflows but is slower.
+//
+// Both versions have the same functions, so the rest of the interpreter does not need to know
+// which one it got.
+#[cfg(not(feature = "bigint"))]
+pub use self::fixed::*;
+
+#[cfg(feature = "bigint")]
+pub use self::big::*;
+
+const OVERFLOW: &str = "integer overflow";
+const DIVISION_BY_ZERO: &str = "division by zero";
+
+#[cfg(not(feature = "bigint"))]
+mod fixed {
+    use super::*;
+
+    pub type Integer = i64;
+
+    // Value of the digits in the given radix, None if it does not fit
+    pub fn parse(digits: &str, radix: u32) -> Option<Integer> {
+        i64::from_str_radix(digits, radix).ok()
+    }
+
+    pub fn add(left: &Integer, right: &Integer) -> Result<Integer, String> {
+        left.checked_add(*right)
+            .ok_or_else(|| String::from(OVERFLOW))
+    }
+
+    pub fn sub(left: &Integer, right: &Integer) -> Result<Integer, String> {
+        left.checked_sub(*right)
+            .ok_or_else(|| String::from(OVERFLOW))
+    }
+
+    pub fn mul(left: &Integer, right: &Integer) -> Result<Integer, String> {
+        left.checked_mul(*right)
+            .ok_or_else(|| String::from(OVERFLOW))
+    }
+
+    // Truncates towards zero. The only overflow is the minimum divided by -1.
+    pub fn div(left: &Integer, right: &Integer) -> Result<Integer, String> {
+        if *right == 0 {
+            return Err(String::from(DIVISION_BY_ZERO));
+        }
+        left.checked_div(*right)
+            .ok_or_else(|| String::from(OVERFLOW))
+    }
+
+    pub fn neg(value: &Integer) -> Result<Integer, String> {
+        value.checked_neg().ok_or_else(|| String::from(OVERFLOW))
+    }
+
+    pub fn from_i64(value: i64) -> Integer {
+        value
+    }
+
+    pub fn to_i64(value: &Integer) -> Option<i64> {
+        Some(*value)
+    }
+
+    pub fn to_usize(value: &Integer) -> Option<usize> {
+        if *value < 0 {
+            return None;
+        }
+        Some(*value as usize)
+    }
+
+    pub fn to_f64(value: &Integer) -> f64 {
+        *value as f64
+    }
+}
+
+#[cfg(feature = "bigint")]
+mod big {
+    use super::*;
+    use num_bigint::BigInt;
+    use num_traits::{ToPrimitive, Zero};
+
+    pub type Integer = BigInt;
+
+    pub fn parse(digits: &str, radix: u32) -> Option<Integer> {
+        BigInt::parse_bytes(digits.as_bytes(), radix)
+    }
+
+    pub fn add(left: &Integer, right: &Integer) -> Result<Integer, String> {
+        Ok(left + right)
+    }
+
+    pub fn sub(left: &Integer, right: &Integer) -> Result<Integer, String> {
+        Ok(left - right)
+    }
+
+    pub fn mul(left: &Integer, right: &Integer) -> Result<Integer, String> {
+        Ok(left * right)
+    }
+
+    // Truncates towards zero, like the 64-bit version
+    pub fn div(left: &Integer, right: &Integer) -> Result<Integer, String> {
+        if right.is_zero() {
+            return Err(String::from(DIVISION_BY_ZERO));
+        }
+        Ok(left / right)
+    }
+
+    pub fn neg(value: &Integer) -> Result<Integer, String> {
+        Ok(-value)
+    }
+
+    pub fn from_i64(value: i64) -> Integer {
+        BigInt::from(value)
+    }
+
+    pub fn to_i64(value: &Integer) -> Option<i64> {
+        value.to_i64()
+    }
+
+    pub fn to_usize(value: &Integer) -> Option<usize> {
+        value.to_usize()
+    }
+
+    // Values too large for a float become infinity, so the NAN for a failed conversion is
+    // never returned
+    pub fn to_f64(value: &Integer) -> f64 {
+        value.to_f64().unwrap_or(f64::NAN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(value: i64) -> Integer {
+        from_i64(value)
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(add(&int(2), &int(3)), Ok(int(5)));
+        assert_eq!(sub(&int(2), &int(3)), Ok(int(-1)));
+        assert_eq!(mul(&int(-4), &int(3)), Ok(int(-12)));
+        assert_eq!(div(&int(7), &int(2)), Ok(int(3)));
+        assert_eq!(div(&int(-7), &int(2)), Ok(int(-3)));
+        assert_eq!(neg(&int(5)), Ok(int(-5)));
+        assert_eq!(div(&int(1), &int(0)), Err(String::from("division by zero")));
+        assert_eq!(parse("ff", 16), Some(int(255)));
+        assert_eq!(to_usize(&int(-1)), None);
+        assert_eq!(to_f64(&int(3)), 3.0);
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn overflow() {
+        let overflow = Err(String::from("integer overflow"));
+
+        assert_eq!(add(&i64::MAX, &1), overflow);
+        assert_eq!(sub(&i64::MIN, &1), overflow);
+        assert_eq!(mul(&i64::MAX, &2), overflow);
+        assert_eq!(div(&i64::MIN, &-1), overflow);
+        assert_eq!(neg(&i64::MIN), overflow);
+        assert_eq!(parse("9223372036854775808", 10), None);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn no_overflow() {
+        let max = int(i64::MAX);
+
+        let sum = add(&max, &int(1)).unwrap();
+        assert_eq!(sum.to_string(), "9223372036854775808");
+        assert_eq!(to_i64(&sum), None);
+        assert_eq!(
+            mul(&max, &max).unwrap().to_string(),
+            "85070591730234615847396907784232501249"
+        );
+        assert_eq!(
+            neg(&int(i64::MIN)).unwrap().to_string(),
+            "9223372036854775808"
+        );
+        assert_eq!(
+            parse("123456789012345678901234567890", 10).map(|x| x.to_string()),
+            Some(String::from("123456789012345678901234567890"))
+        );
+
+        let huge = parse(&format!("1{}", "0".repeat(400)), 10).unwrap();
+        assert_eq!(to_f64(&huge), f64::INFINITY);
+        assert_eq!(to_f64(&neg(&huge).unwrap()), f64::NEG_INFINITY);
+    }
+}
@@ -1,11 +1,20 @@
+use crate::diagnostic::Diagnostic;
+use crate::integer::{self, Integer};
 use crate::token::*;
-use std::convert::TryInto;
+use std::collections::VecDeque;
+use unicode_xid::UnicodeXID;
 
 pub struct Lexer<'a> {
     input: &'a str,
-    position: usize,      // current position in input (points to current char)
-    read_position: usize, // current reading position in input (after current char)
+    position: usize,      // byte offset of the current char in input
+    read_position: usize, // byte offset of the next char in input (after current char)
     ch: char,             // current char under examination
+    line: usize,          // line of the current char, starting at 1
+    column: usize,        // column of the current char, starting at 1
+    errors: Vec<Diagnostic>,
+    keep_comments: bool, // hand out comments as COMMENT tokens instead of skipping them
+    lookahead: VecDeque<Token<'a>>, // tokens lexed by peek but not handed out yet
+    finished: bool,      // the iterator returned EOF
 }
 
 impl<'a> Lexer<'a> {
@@ -15,24 +24,49 @@ impl<'a> Lexer<'a> {
             position: 0,
             read_position: 0,
             ch: '\0',
+            line: 1,
+            column: 1,
+            errors: vec![],
+            keep_comments: false,
+            lookahead: VecDeque::new(),
+            finished: false,
         };
         l.read_char();
         l
     }
 
-    // Read the current character
+    // Lexer for tools like a formatter, which need to keep the comments of the input. The
+    // parser does not expect COMMENT tokens, so this one can't be used to parse a program.
+    pub fn with_comments(input: &'a str) -> Self {
+        let mut l = Lexer::new(input);
+        l.keep_comments = true;
+        l
+    }
+
+    // Read the current character. Only the char at the cursor is decoded, so lexing the whole
+    // input is linear in its length.
     fn read_char(&mut self) {
-        if self.read_position >= self.input.chars().count() {
-            self.ch = '\0';
-        } else {
-            self.ch = self
-                .input
-                .chars()
-                .nth(self.read_position.try_into().unwrap())
-                .unwrap()
+        // Move the location past the char we are leaving behind
+        if self.read_position > 0 && self.position < self.input.len() {
+            if self.ch == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+
+        match self.input[self.read_position..].chars().next() {
+            Some(ch) => {
+                self.ch = ch;
+                self.position = self.read_position;
+                self.read_position += ch.len_utf8();
+            }
+            None => {
+                self.ch = '\0';
+                self.position = self.input.len();
+            }
         }
-        self.position = self.read_position;
-        self.read_position += 1;
     }
 
     // Skip the whitespace characters in the input
@@ -44,184 +78,416 @@ impl<'a> Lexer<'a> {
 
     // Main method which return the next token from the input.
     pub fn next_token(&mut self) -> Token<'a> {
-        self.skip_whitespace(); // We need to skip the whitespace and the new lines from the input
+        match self.lookahead.pop_front() {
+            Some(tok) => tok,
+            None => self.lex_token(),
+        }
+    }
 
-        match self.ch {
-            '=' => {
-                if self.peek_char() == '=' {
-                    let ch = self.ch;
-                    self.read_char();
-                    let mut tok = self.new_token(EQ, ch);
-                    let mut new_literal = ch.to_string();
-                    new_literal.push(self.ch);
-                    tok.literal = new_literal;
-                    self.read_char();
-                    tok
-                } else {
-                    let tok = self.new_token(ASSIGN, self.ch);
-                    self.read_char();
-                    tok
-                }
-            }
-            ';' => {
-                let tok = self.new_token(SEMICOLON, self.ch);
-                self.read_char();
-                tok
+    // Token `n` places ahead without consuming anything, peek(0) is the one next_token returns
+    // next. Past the end of the input it is EOF. The problems found in the tokens peeked at are
+    // in take_errors right away.
+    pub fn peek(&mut self, n: usize) -> Token<'a> {
+        while self.lookahead.len() <= n {
+            let tok = self.lex_token();
+            self.lookahead.push_back(tok);
+        }
+        self.lookahead[n]
+    }
+
+    fn lex_token(&mut self) -> Token<'a> {
+        loop {
+            self.skip_whitespace(); // We need to skip the whitespace and the new lines from the input
+
+            let (start, line, column) = (self.position, self.line, self.column);
+            let kind = self.read_token();
+            let tok = Token {
+                kind,
+                lexeme: &self.input[start..self.position],
+                span: Span {
+                    start,
+                    end: self.position,
+                    line,
+                    column,
+                },
+            };
+
+            if tok.kind == COMMENT && !self.keep_comments {
+                continue;
             }
-            '(' => {
-                let tok = self.new_token(LPAREN, self.ch);
-                self.read_char();
-                tok
+            if tok.kind == ILLEGAL {
+                let msg = format!("unexpected character `{}`", tok.lexeme);
+                self.errors.push(Diagnostic::error("E0001", msg, tok.span));
             }
-            ')' => {
-                let tok = self.new_token(RPAREN, self.ch);
+            return tok;
+        }
+    }
+
+    // Hand over the problems found so far, the lexer keeps going after an illegal character
+    pub fn take_errors(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.errors)
+    }
+
+    // Read the token starting at the current char and advance past it
+    fn read_token(&mut self) -> TokenKind {
+        let kind = match self.ch {
+            '=' if self.peek_char() == '=' => {
                 self.read_char();
-                tok
+                EQ
             }
-            ',' => {
-                let tok = self.new_token(COMMA, self.ch);
+            '=' => ASSIGN,
+            ';' => SEMICOLON,
+            ':' => COLON,
+            '(' => LPAREN,
+            ')' => RPAREN,
+            ',' => COMMA,
+            '+' => PLUS,
+            '*' => ASTERISK,
+            '-' => MINUS,
+            '!' if self.peek_char() == '=' => {
                 self.read_char();
-                tok
+                NOTEQ
             }
-            '+' => {
-                let tok = self.new_token(PLUS, self.ch);
-                self.read_char();
-                tok
+            '!' => BANG,
+            '/' if self.peek_char() == '/' || self.peek_char() == '*' => {
+                self.read_comment();
+                return COMMENT;
             }
-            '*' => {
-                let tok = self.new_token(ASTERISK, self.ch);
-                self.read_char();
-                tok
+            '/' => SLASH,
+            '<' => LT,
+            '>' => GT,
+            '{' => LBRACE,
+            '}' => RBRACE,
+            '[' => LBRACKET,
+            ']' => RBRACKET,
+            '"' => {
+                self.read_string();
+                return STRING;
             }
-            '-' => {
-                let tok = self.new_token(MINUS, self.ch);
-                self.read_char();
-                tok
+            // This happens when there is no more characters i.e. end of the input
+            '\0' if self.position == self.input.len() => return EOF,
+            ch if is_identifier_start(ch) => {
+                // Some words are specific for the language(keywords) and we need to distinguish
+                // that from the identifiers chosen by the user(function names, variables, etc).
+                // We need to lookup every word if it matches any of the keywords
+                return Token::lookup_ident(self.read_identifier());
             }
-            '!' => {
-                if self.peek_char() == '=' {
-                    let ch = self.ch;
-                    self.read_char();
-                    let mut tok = self.new_token(NOTEQ, ch);
-                    let mut new_literal = ch.to_string();
-                    new_literal.push(self.ch);
-                    tok.literal = new_literal;
+            ch if ch.is_ascii_digit() => return self.read_number(),
+            // Map any unrecognizable char as illegal
+            _ => ILLEGAL,
+        };
+
+        self.read_char();
+        kind
+    }
+
+    // Read a string literal starting at the opening quote and return its contents with the
+    // escape sequences replaced by the characters they stand for
+    fn read_string(&mut self) -> String {
+        let open = self.current_span();
+        let mut out = String::new();
+        self.read_char();
+
+        loop {
+            match self.ch {
+                '"' => {
                     self.read_char();
-                    tok
-                } else {
-                    let tok = self.new_token(BANG, self.ch);
+                    return out;
+                }
+                '\0' if self.position == self.input.len() => {
+                    let msg = String::from("unterminated string");
+                    self.errors.push(
+                        Diagnostic::error("E0005", msg, open)
+                            .with_label(String::from("string starts here")),
+                    );
+                    return out;
+                }
+                '\\' => {
+                    if let Some(ch) = self.read_escape() {
+                        out.push(ch);
+                    }
+                }
+                ch => {
+                    out.push(ch);
                     self.read_char();
-                    tok
                 }
             }
-            '/' => {
-                let tok = self.new_token(SLASH, self.ch);
+        }
+    }
+
+    // Read a comment and return it including its delimiters. A `//` comment runs up to the end
+    // of the line, a `/* */` comment up to its matching `*/` as block comments can be nested.
+    fn read_comment(&mut self) -> &'a str {
+        let position = self.position;
+
+        if self.peek_char() == '/' {
+            while self.ch != '\n' && self.position < self.input.len() {
                 self.read_char();
-                tok
             }
-            '<' => {
-                let tok = self.new_token(LT, self.ch);
-                self.read_char();
-                tok
+            return &self.input[position..self.position];
+        }
+
+        let open = Span {
+            end: self.position + 2,
+            ..self.current_span()
+        };
+        let mut depth = 0;
+        loop {
+            if self.position == self.input.len() {
+                let msg = String::from("unterminated block comment");
+                self.errors.push(
+                    Diagnostic::error("E0007", msg, open)
+                        .with_label(String::from("comment starts here")),
+                );
+                break;
             }
-            '>' => {
-                let tok = self.new_token(GT, self.ch);
+
+            if self.ch == '/' && self.peek_char() == '*' {
+                depth += 1;
                 self.read_char();
-                tok
-            }
-            '{' => {
-                let tok = self.new_token(LBRACE, self.ch);
+            } else if self.ch == '*' && self.peek_char() == '/' {
+                depth -= 1;
                 self.read_char();
-                tok
             }
-            '}' => {
-                let tok = self.new_token(RBRACE, self.ch);
-                self.read_char();
-                tok
+            self.read_char();
+
+            if depth == 0 {
+                break;
             }
-            // This happens when there is no more characters i.e. end of the input
-            '\0' => Token {
-                ttype: EOF,
-                literal: String::from(""),
-            },
-            _ => {
-                let mut tok = Token::new();
-
-                if self.ch.is_alphabetic() {
-                    // Some words are specific for the language(keywords) and we need to distinguish
-                    // that from the identifiers chosen by the user(function names, variables, etc).
-                    // We need to lookup every word if it matches any of the keywords
-                    tok.literal = self.read_identifier();
-                    tok.ttype = Token::lookup_ident(&tok.literal);
-                    tok
-                } else if self.ch.is_numeric() {
-                    // Any consecutive digits(0-9) are matched as single INT token
-                    tok.ttype = INT;
-                    tok.literal = self.read_number();
-                    tok
-                } else {
-                    // Map any unrecognizable char as illegal
-                    let tok = self.new_token(ILLEGAL, self.ch);
+        }
+
+        &self.input[position..self.position]
+    }
+
+    // Read an escape sequence starting at the backslash. Supported are \n, \t, \", \\ and
+    // \u{...} with up to six hex digits.
+    fn read_escape(&mut self) -> Option<char> {
+        let start = self.current_span();
+        self.read_char();
+
+        let ch = match self.ch {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            '"' => Some('"'),
+            '\\' => Some('\\'),
+            'u' if self.peek_char() == '{' => {
+                self.read_char();
+                let digits = self.read_position;
+                while self.peek_char().is_ascii_hexdigit() {
+                    self.read_char();
+                }
+                let value = &self.input[digits..self.read_position];
+                if self.peek_char() != '}' {
                     self.read_char();
-                    tok
+                    self.escape_error(start, "unterminated unicode escape");
+                    return None;
                 }
+                self.read_char();
+
+                let ch = u32::from_str_radix(value, 16)
+                    .ok()
+                    .and_then(std::char::from_u32);
+                if value.is_empty() || value.len() > 6 || ch.is_none() {
+                    self.read_char();
+                    self.escape_error(start, "invalid unicode escape");
+                    return None;
+                }
+                ch
+            }
+            // Leave the end of the input for read_string to report
+            '\0' if self.position == self.input.len() => return None,
+            _ => {
+                self.read_char();
+                self.escape_error(start, "unknown escape sequence");
+                return None;
             }
+        };
+
+        self.read_char();
+        ch
+    }
+
+    // Report the escape sequence from `start` up to the current char
+    fn escape_error(&mut self, start: Span, msg: &str) {
+        let span = Span {
+            end: self.position,
+            ..start
+        };
+        let msg = format!("{} `{}`", msg, &self.input[span.start..span.end]);
+        self.errors.push(Diagnostic::error("E0006", msg, span));
+    }
+
+    // Span of the current char
+    fn current_span(&self) -> Span {
+        Span {
+            start: self.position,
+            end: self.read_position.max(self.position),
+            line: self.line,
+            column: self.column,
         }
     }
 
-    // Return a number if consecutive digits(0-9) are found
-    fn read_number(&mut self) -> String {
-        let position = self.position;
+    // Read a number: digits, then optionally a fraction (`.5`) and an exponent (`e-3`). With
+    // either of them it is a FLOAT, otherwise an INT. The `.` and the `e` only belong to the
+    // number when digits follow, so `1.` is the integer 1 and an illegal `.`. Integers can also
+    // be written in hex (0xff), octal (0o17) or binary (0b101).
+    fn read_number(&mut self) -> TokenKind {
+        if self.ch == '0' && matches!(self.peek_char(), 'x' | 'o' | 'b') {
+            // Everything up to the end of the word, integer_value tells what is wrong with it
+            // when the digits don't match the prefix
+            self.read_char();
+            self.read_char();
+            while self.ch.is_ascii_alphanumeric() || self.ch == '_' {
+                self.read_char();
+            }
+            return INT;
+        }
 
-        while self.ch.is_numeric() {
-            self.read_char()
+        let mut kind = INT;
+        self.read_digits();
+
+        if self.ch == '.' && self.peek_char().is_ascii_digit() {
+            kind = FLOAT;
+            self.read_char();
+            self.read_digits();
+        }
+
+        if self.ch == 'e' || self.ch == 'E' {
+            let rest = &self.input[self.read_position..];
+            let sign = if rest.starts_with('+') || rest.starts_with('-') {
+                1
+            } else {
+                0
+            };
+            if rest[sign..].starts_with(|ch: char| ch.is_ascii_digit()) {
+                kind = FLOAT;
+                for _ in 0..=sign {
+                    self.read_char();
+                }
+                self.read_digits();
+            }
         }
 
-        self.input[position..self.position].to_string()
+        kind
     }
 
-    // Underscore is also treated as a letter
-    fn is_letter(&self, ch: char) -> bool {
-        if ch.is_ascii_alphabetic() || ch == '_' {
-            true
-        } else {
-            false
+    // Digits can be grouped with underscores: 1_000_000. All of them are read here, misplaced
+    // ones are reported by integer_value and float_value.
+    fn read_digits(&mut self) {
+        while self.ch.is_ascii_digit() || self.ch == '_' {
+            self.read_char()
         }
     }
 
-    // Read consecutive letters and return identifier
-    fn read_identifier(&mut self) -> String {
+    // Read the rest of the identifier starting at the current char
+    fn read_identifier(&mut self) -> &'a str {
         let position = self.position;
 
         loop {
-            if self.is_letter(self.ch) {
+            if is_identifier_continue(self.ch) {
                 self.read_char();
             } else {
                 break;
             }
         }
 
-        self.input[position..self.position].to_string()
+        &self.input[position..self.position]
+    }
+
+    fn peek_char(&self) -> char {
+        self.input[self.read_position..]
+            .chars()
+            .next()
+            .unwrap_or('\0')
     }
+}
+
+// Tokens up to and including EOF
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
 
-    fn new_token(&self, ttype: TokenType<'a>, ch: char) -> Token<'a> {
-        Token {
-            ttype,
-            literal: ch.to_string(),
+    fn next(&mut self) -> Option<Token<'a>> {
+        if self.finished {
+            return None;
         }
+
+        let tok = self.next_token();
+        self.finished = tok.kind == EOF;
+        Some(tok)
     }
-    fn peek_char(&self) -> char {
-        if self.read_position >= self.input.chars().count() {
-            '\0'
-        } else {
-            self.input.chars().nth(self.read_position).unwrap()
+}
+
+// Value of a string token: its lexeme without the quotes, escape sequences replaced by the
+// characters they stand for. Invalid escape sequences are left out, the lexer reported them.
+pub fn string_value(lexeme: &str) -> String {
+    Lexer::new(lexeme).read_string()
+}
+
+// Value of an INT token, which has to fit in 64 bits unless the `bigint` feature is enabled
+pub fn integer_value(lexeme: &str) -> Result<Integer, String> {
+    let (radix, name, digits) = match lexeme.get(..2) {
+        Some("0x") => (16, "hexadecimal", &lexeme[2..]),
+        Some("0o") => (8, "octal", &lexeme[2..]),
+        Some("0b") => (2, "binary", &lexeme[2..]),
+        _ => (10, "decimal", lexeme),
+    };
+
+    if digits.chars().all(|ch| ch == '_') {
+        return Err(format!("missing digits after `{}`", &lexeme[..2]));
+    }
+    if let Some(ch) = digits.chars().find(|&ch| ch != '_' && !ch.is_digit(radix)) {
+        return Err(format!("invalid digit `{}` in {} literal", ch, name));
+    }
+    check_separators(digits)?;
+    integer::parse(&digits.replace('_', ""), radix)
+        .ok_or_else(|| format!("integer literal is too large, the maximum is {}", i64::MAX))
+}
+
+// Value of a FLOAT token
+pub fn float_value(lexeme: &str) -> Result<f64, String> {
+    let (mantissa, exponent) = match lexeme.find(['e', 'E']) {
+        Some(i) => lexeme.split_at(i),
+        None => (lexeme, ""),
+    };
+    if exponent.contains('_') {
+        return Err(String::from("`_` can't be used in an exponent"));
+    }
+    check_separators(mantissa)?;
+
+    match lexeme.replace('_', "").parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        Ok(_) => Err(String::from("float literal is too large")),
+        Err(_) => Err(format!("could not parse {} as float", lexeme)),
+    }
+}
+
+// Every `_` in the digits of a number has to be between two digits: 1_000 but not 1__000,
+// 1_ or 0x_ff. The digits have been checked already, so anything else is `_` or `.`.
+fn check_separators(digits: &str) -> Result<(), String> {
+    let bytes = digits.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        let is_digit = |j: usize| bytes.get(j).is_some_and(|&b| b != b'_' && b != b'.');
+        if b == b'_' && (i == 0 || !is_digit(i - 1) || !is_digit(i + 1)) {
+            return Err(String::from("`_` can only separate two digits"));
         }
     }
+    Ok(())
+}
+
+// Identifiers follow Unicode UAX #31: they start with a letter (XID_Start) or an underscore,
+// the other chars can also be digits and combining marks (XID_Continue, which includes `_`)
+pub fn is_identifier_start(ch: char) -> bool {
+    ch == '_' || ch.is_xid_start()
+}
+
+pub fn is_identifier_continue(ch: char) -> bool {
+    ch.is_xid_continue()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::lexer::Lexer;
+    use crate::integer;
+    use crate::lexer::{float_value, integer_value, string_value, Lexer};
     use crate::token::*;
 
     // Testing the monkey language tokens
@@ -234,7 +500,7 @@ mod tests {
                             };
 
                             let result = add(five, ten);
-                            !-/*5;
+                            !-/ *5;
                             5 < 10 > 5;
 
                             if (5 < 10) {
@@ -245,22 +511,497 @@ mod tests {
 
                             10 == 10;
                             10 != 9;
+                            [1, 2];
+                            {"foo": "bar"}
                             "#;
 
-        let mut l = Lexer::new(&input);
-        let token_types = vec![
+        let mut l = Lexer::new(input);
+        let token_kinds = vec![
             LET, IDENT, ASSIGN, INT, SEMICOLON, LET, IDENT, ASSIGN, INT, SEMICOLON, LET, IDENT,
             ASSIGN, FUNCTION, LPAREN, IDENT, COMMA, IDENT, RPAREN, LBRACE, IDENT, PLUS, IDENT,
             SEMICOLON, RBRACE, SEMICOLON, LET, IDENT, ASSIGN, IDENT, LPAREN, IDENT, COMMA, IDENT,
             RPAREN, SEMICOLON, BANG, MINUS, SLASH, ASTERISK, INT, SEMICOLON, INT, LT, INT, GT, INT,
             SEMICOLON, IF, LPAREN, INT, LT, INT, RPAREN, LBRACE, RETURN, TRUE, SEMICOLON, RBRACE,
             ELSE, LBRACE, RETURN, FALSE, SEMICOLON, RBRACE, INT, EQ, INT, SEMICOLON, INT, NOTEQ,
-            INT, SEMICOLON, EOF,
+            INT, SEMICOLON, LBRACKET, INT, COMMA, INT, RBRACKET, SEMICOLON, LBRACE, STRING, COLON,
+            STRING, RBRACE, EOF,
+        ];
+
+        for kind in token_kinds {
+            let tok = l.next_token();
+            assert_eq!(tok.kind, kind)
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        let input = "5 1.5 0.25 10.0 1e3 1.5e-3 2E+10 1. 3.x 4e 5e+ 7.e2 1.2.3";
+
+        let mut l = Lexer::new(input);
+        let expected = vec![
+            (INT, "5"),
+            (FLOAT, "1.5"),
+            (FLOAT, "0.25"),
+            (FLOAT, "10.0"),
+            (FLOAT, "1e3"),
+            (FLOAT, "1.5e-3"),
+            (FLOAT, "2E+10"),
+            (INT, "1"),
+            (ILLEGAL, "."),
+            (INT, "3"),
+            (ILLEGAL, "."),
+            (IDENT, "x"),
+            (INT, "4"),
+            (IDENT, "e"),
+            (INT, "5"),
+            (IDENT, "e"),
+            (PLUS, "+"),
+            (INT, "7"),
+            (ILLEGAL, "."),
+            (IDENT, "e2"),
+            (FLOAT, "1.2"),
+            (ILLEGAL, "."),
+            (INT, "3"),
+            (EOF, ""),
+        ];
+
+        for (kind, lexeme) in expected {
+            let tok = l.next_token();
+            assert_eq!(tok.kind, kind, "got={:?}", tok);
+            assert_eq!(tok.lexeme, lexeme);
+        }
+    }
+
+    #[test]
+    fn prefixed_and_separated_numbers() {
+        let input =
+            "0xff 0o17 0b1010 1_000_000 0x_FF_FF 1_000.000_5 1e1_0 0x 0b102 0xfg 1__0 ٣ 0.5_";
+
+        let mut l = Lexer::new(input);
+        let expected = vec![
+            (INT, "0xff"),
+            (INT, "0o17"),
+            (INT, "0b1010"),
+            (INT, "1_000_000"),
+            (INT, "0x_FF_FF"),
+            (FLOAT, "1_000.000_5"),
+            (FLOAT, "1e1_0"),
+            (INT, "0x"),
+            (INT, "0b102"),
+            (INT, "0xfg"),
+            (INT, "1__0"),
+            (ILLEGAL, "٣"),
+            (FLOAT, "0.5_"),
+            (EOF, ""),
+        ];
+
+        for (kind, lexeme) in expected {
+            let tok = l.next_token();
+            assert_eq!(tok.kind, kind, "got={:?}", tok);
+            assert_eq!(tok.lexeme, lexeme);
+        }
+    }
+
+    #[test]
+    fn integer_values() {
+        let tests = vec![
+            ("0", Ok(0)),
+            ("42", Ok(42)),
+            ("0xff", Ok(255)),
+            ("0xFF", Ok(255)),
+            ("0o17", Ok(15)),
+            ("0b1010", Ok(10)),
+            ("1_000_000", Ok(1000000)),
+            ("0b1111_0000", Ok(240)),
+            ("9223372036854775807", Ok(i64::MAX)),
+            ("0x7fff_ffff_ffff_ffff", Ok(i64::MAX)),
+            ("0x", Err("missing digits after `0x`")),
+            ("0b__", Err("missing digits after `0b`")),
+            ("0b102", Err("invalid digit `2` in binary literal")),
+            ("0o8", Err("invalid digit `8` in octal literal")),
+            ("0xfg", Err("invalid digit `g` in hexadecimal literal")),
+            ("0b_1111_0000", Err("`_` can only separate two digits")),
+            ("0x_FF", Err("`_` can only separate two digits")),
+            ("1__0", Err("`_` can only separate two digits")),
+            ("1_", Err("`_` can only separate two digits")),
+        ];
+
+        for (lexeme, expected) in tests {
+            assert_eq!(
+                integer_value(lexeme),
+                expected.map(integer::from_i64).map_err(String::from),
+                "lexeme {}",
+                lexeme
+            );
+        }
+
+        for lexeme in &["9223372036854775808", "0x8000000000000000"] {
+            let value = integer_value(lexeme).map(|x| x.to_string());
+            if cfg!(feature = "bigint") {
+                assert_eq!(value, Ok(String::from("9223372036854775808")));
+            } else {
+                assert_eq!(
+                    value,
+                    Err(String::from(
+                        "integer literal is too large, the maximum is 9223372036854775807"
+                    ))
+                );
+            }
+        }
+
+        assert_eq!(float_value("1_000.5"), Ok(1000.5));
+        assert_eq!(float_value("1_000.000_5e-10"), Ok(1000.0005e-10));
+        for lexeme in &["0.5_", "1_.5", "1__0.5"] {
+            assert_eq!(
+                float_value(lexeme),
+                Err(String::from("`_` can only separate two digits")),
+                "lexeme {}",
+                lexeme
+            );
+        }
+        assert_eq!(
+            float_value("1e1_0"),
+            Err(String::from("`_` can't be used in an exponent"))
+        );
+        assert_eq!(
+            float_value("1e999"),
+            Err(String::from("float literal is too large"))
+        );
+    }
+
+    #[test]
+    fn iterator() {
+        let kinds: Vec<TokenKind> = Lexer::new("let x = 5;").map(|tok| tok.kind).collect();
+        assert_eq!(kinds, vec![LET, IDENT, ASSIGN, INT, SEMICOLON, EOF]);
+
+        let mut l = Lexer::new("");
+        assert_eq!(l.next().map(|tok| tok.kind), Some(EOF));
+        assert_eq!(l.next(), None);
+        assert_eq!(l.next(), None);
+    }
+
+    #[test]
+    fn lookahead() {
+        let mut l = Lexer::new("{ x: 1 }");
+
+        assert_eq!(l.peek(1).kind, IDENT);
+        assert_eq!(l.peek(2).lexeme, ":");
+        assert_eq!(l.peek(0).kind, LBRACE);
+        assert_eq!(l.next_token().kind, LBRACE);
+        assert_eq!(l.peek(0).kind, IDENT);
+        assert_eq!(l.peek(5).kind, EOF);
+        assert_eq!(l.peek(9).kind, EOF);
+
+        let kinds: Vec<TokenKind> = l.map(|tok| tok.kind).collect();
+        assert_eq!(kinds, vec![IDENT, COLON, INT, RBRACE, EOF]);
+    }
+
+    #[test]
+    fn lookahead_errors() {
+        let mut l = Lexer::new("x @ y");
+
+        assert_eq!(l.peek(2).kind, IDENT);
+        let errors = l.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "unexpected character `@`");
+
+        assert_eq!(l.next_token().kind, IDENT);
+        assert_eq!(l.next_token().kind, ILLEGAL);
+        assert!(l.take_errors().is_empty());
+    }
+
+    #[test]
+    fn non_ascii_input() {
+        let input = "let € = 5; let x = 10;";
+
+        let mut l = Lexer::new(input);
+        let expected = vec![
+            (LET, "let", 0),
+            (ILLEGAL, "€", 4),
+            (ASSIGN, "=", 8),
+            (INT, "5", 10),
+            (SEMICOLON, ";", 11),
+            (LET, "let", 13),
+            (IDENT, "x", 17),
+            (ASSIGN, "=", 19),
+            (INT, "10", 21),
+            (SEMICOLON, ";", 23),
+        ];
+
+        for (kind, literal, start) in expected {
+            let tok = l.next_token();
+            assert_eq!(tok.kind, kind);
+            assert_eq!(tok.lexeme, literal);
+            assert_eq!(tok.span.start, start);
+            assert_eq!(&input[tok.span.start..tok.span.end], literal);
+        }
+    }
+
+    #[test]
+    fn identifiers() {
+        let input = "_foo x1 _ __init__ café naïve 名前 переменная λx δ2 x_ñ_1 e\u{301}t\u{e9};";
+
+        let mut l = Lexer::new(input);
+        let expected = vec![
+            "_foo",
+            "x1",
+            "_",
+            "__init__",
+            "café",
+            "naïve",
+            "名前",
+            "переменная",
+            "λx",
+            "δ2",
+            "x_ñ_1",
+            "e\u{301}t\u{e9}",
         ];
 
-        for token_type in token_types {
+        for literal in expected {
             let tok = l.next_token();
-            assert_eq!(tok.ttype, token_type)
+            assert_eq!(tok.kind, IDENT, "got={:?}", tok);
+            assert_eq!(tok.lexeme, literal);
+            assert_eq!(&input[tok.span.start..tok.span.end], literal);
+        }
+        assert_eq!(l.next_token().kind, SEMICOLON);
+        assert!(l.take_errors().is_empty());
+    }
+
+    #[test]
+    fn identifier_boundaries() {
+        let input = "1x é+ü letx fn_ ×y \u{301}a";
+
+        let mut l = Lexer::new(input);
+        let expected = vec![
+            (INT, "1"),
+            (IDENT, "x"),
+            (IDENT, "é"),
+            (PLUS, "+"),
+            (IDENT, "ü"),
+            (IDENT, "letx"),
+            (IDENT, "fn_"),
+            (ILLEGAL, "×"),
+            (IDENT, "y"),
+            (ILLEGAL, "\u{301}"),
+            (IDENT, "a"),
+            (EOF, ""),
+        ];
+
+        for (kind, literal) in expected {
+            let tok = l.next_token();
+            assert_eq!(tok.kind, kind, "got={:?}", tok);
+            assert_eq!(tok.lexeme, literal);
+        }
+    }
+
+    #[test]
+    fn illegal_character_errors() {
+        let mut l = Lexer::new("let x = 5 @ 3;");
+        while l.next_token().kind != EOF {}
+
+        let errors = l.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "E0001");
+        assert_eq!(errors[0].message, "unexpected character `@`");
+        assert_eq!((errors[0].span.start, errors[0].span.end), (10, 11));
+        assert!(l.take_errors().is_empty());
+    }
+
+    #[test]
+    fn strings() {
+        let input = r#""foobar" "foo bar" "" "a\nb" "\tx\"y\"\\" "\u{48}\u{e9}\u{1F600}";"#;
+
+        let mut l = Lexer::new(input);
+        let expected = vec![
+            (STRING, "foobar"),
+            (STRING, "foo bar"),
+            (STRING, ""),
+            (STRING, "a\nb"),
+            (STRING, "\tx\"y\"\\"),
+            (STRING, "Hé😀"),
+            (SEMICOLON, ";"),
+            (EOF, ""),
+        ];
+
+        for (kind, value) in expected {
+            let tok = l.next_token();
+            assert_eq!(tok.kind, kind);
+            if kind == STRING {
+                assert_eq!(string_value(tok.lexeme), value);
+            } else {
+                assert_eq!(tok.lexeme, value);
+            }
+        }
+        assert!(l.take_errors().is_empty());
+    }
+
+    #[test]
+    fn string_spans() {
+        let input = "let s = \"a\\tb\";";
+
+        let mut l = Lexer::new(input);
+        l.next_token();
+        l.next_token();
+        l.next_token();
+        let tok = l.next_token();
+        assert_eq!(tok.kind, STRING);
+        assert_eq!(&input[tok.span.start..tok.span.end], "\"a\\tb\"");
+        assert_eq!(l.next_token().kind, SEMICOLON);
+    }
+
+    #[test]
+    fn string_errors() {
+        let tests = vec![
+            ("\"abc", "E0005", "unterminated string", (0, 1), "abc"),
+            ("\"ab\\", "E0005", "unterminated string", (0, 1), "ab"),
+            (
+                "\"a\\qb\"",
+                "E0006",
+                "unknown escape sequence `\\q`",
+                (2, 4),
+                "ab",
+            ),
+            (
+                "\"\\u{110000}\"",
+                "E0006",
+                "invalid unicode escape `\\u{110000}`",
+                (1, 11),
+                "",
+            ),
+            (
+                "\"\\u{}x\"",
+                "E0006",
+                "invalid unicode escape `\\u{}`",
+                (1, 5),
+                "x",
+            ),
+            (
+                "\"\\u{4g}\"",
+                "E0006",
+                "unterminated unicode escape `\\u{4`",
+                (1, 5),
+                "g}",
+            ),
+        ];
+
+        for (input, code, message, (start, end), value) in tests {
+            let mut l = Lexer::new(input);
+            let tok = l.next_token();
+            assert_eq!(tok.kind, STRING, "input {:?}", input);
+            assert_eq!(tok.lexeme, input, "input {:?}", input);
+            assert_eq!(string_value(tok.lexeme), value, "input {:?}", input);
+            assert_eq!(l.next_token().kind, EOF, "input {:?}", input);
+
+            let errors = l.take_errors();
+            assert_eq!(errors.len(), 1, "input {:?}, got={:?}", input, errors);
+            assert_eq!(errors[0].code, code);
+            assert_eq!(errors[0].message, message);
+            assert_eq!((errors[0].span.start, errors[0].span.end), (start, end));
+        }
+    }
+
+    #[test]
+    fn comments() {
+        let input = "// leading comment
+let x = 5; // trailing comment
+/* block */ let /* inline */ y = 10 / 2;
+/* outer /* nested */ still a comment */ x
+//";
+
+        let mut l = Lexer::new(input);
+        let expected = vec![
+            LET, IDENT, ASSIGN, INT, SEMICOLON, LET, IDENT, ASSIGN, INT, SLASH, INT, SEMICOLON,
+            IDENT, EOF,
+        ];
+
+        for kind in expected {
+            let tok = l.next_token();
+            assert_eq!(tok.kind, kind, "got={:?}", tok);
+        }
+        assert!(l.take_errors().is_empty());
+    }
+
+    #[test]
+    fn comment_tokens() {
+        let input = "// one\nx /* two /* three */ */;/**/";
+
+        let mut l = Lexer::with_comments(input);
+        let expected = vec![
+            (COMMENT, "// one", 0, 1, 1),
+            (IDENT, "x", 7, 2, 1),
+            (COMMENT, "/* two /* three */ */", 9, 2, 3),
+            (SEMICOLON, ";", 30, 2, 24),
+            (COMMENT, "/**/", 31, 2, 25),
+            (EOF, "", 35, 2, 29),
+        ];
+
+        for (kind, literal, start, line, column) in expected {
+            let tok = l.next_token();
+            assert_eq!(tok.kind, kind);
+            assert_eq!(tok.lexeme, literal);
+            assert_eq!(&input[tok.span.start..tok.span.end], literal);
+            assert_eq!(
+                (tok.span.start, tok.span.line, tok.span.column),
+                (start, line, column),
+                "wrong span for {:?}",
+                tok
+            );
+        }
+    }
+
+    #[test]
+    fn comment_errors() {
+        let tests = vec![
+            ("/* abc", (0, 2)),
+            ("x /* a /* b */", (2, 4)),
+            ("/* a */ /* /* b */\n", (8, 10)),
+            ("/*/", (0, 2)),
+        ];
+
+        for (input, (start, end)) in tests {
+            let mut l = Lexer::new(input);
+            while l.next_token().kind != EOF {}
+
+            let errors = l.take_errors();
+            assert_eq!(errors.len(), 1, "input {:?}, got={:?}", input, errors);
+            assert_eq!(errors[0].code, "E0007");
+            assert_eq!(errors[0].message, "unterminated block comment");
+            assert_eq!((errors[0].span.start, errors[0].span.end), (start, end));
+        }
+    }
+
+    #[test]
+    fn token_spans() {
+        let input = "let five = 5;\n  five == 10;";
+
+        let mut l = Lexer::new(input);
+        let expected = vec![
+            (LET, 0, 3, 1, 1),
+            (IDENT, 4, 8, 1, 5),
+            (ASSIGN, 9, 10, 1, 10),
+            (INT, 11, 12, 1, 12),
+            (SEMICOLON, 12, 13, 1, 13),
+            (IDENT, 16, 20, 2, 3),
+            (EQ, 21, 23, 2, 8),
+            (INT, 24, 26, 2, 11),
+            (SEMICOLON, 26, 27, 2, 13),
+            (EOF, 27, 27, 2, 14),
+        ];
+
+        for (kind, start, end, line, column) in expected {
+            let tok = l.next_token();
+            assert_eq!(tok.kind, kind);
+            assert_eq!(
+                tok.span,
+                Span {
+                    start,
+                    end,
+                    line,
+                    column
+                },
+                "wrong span for {:?}",
+                tok
+            );
         }
     }
 }
@@ -2,7 +2,29 @@
 
 mod ast;
 mod base;
+mod builtins;
+mod code;
+mod compiler;
+mod convert;
+mod diagnostic;
+mod editor;
+mod engine;
+mod environment;
+mod eval;
+mod integer;
 mod lexer;
+mod object;
 mod parser;
 mod repl;
+mod runner;
+mod symbol_table;
 mod token;
+mod vm;
+
+pub use crate::base::main;
+pub use crate::convert::{FromObject, HostFn, IntoObject};
+pub use crate::diagnostic::{Diagnostic, Label, RenderMode, Severity};
+pub use crate::engine::{Engine, Error};
+pub use crate::integer::Integer;
+pub use crate::object::{HashKey, Object};
+pub use crate::token::Span;
@@ -0,0 +1,191 @@
+use crate::ast::*;
+use crate::code::Instructions;
+use crate::environment::Environment;
+use crate::integer::Integer;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+pub const INTEGER_OBJ: &str = "INTEGER";
+pub const FLOAT_OBJ: &str = "FLOAT";
+pub const BOOLEAN_OBJ: &str = "BOOLEAN";
+pub const STRING_OBJ: &str = "STRING";
+pub const ARRAY_OBJ: &str = "ARRAY";
+pub const HASH_OBJ: &str = "HASH";
+pub const BUILTIN_OBJ: &str = "BUILTIN";
+pub const NULL_OBJ: &str = "NULL";
+pub const RETURN_VALUE_OBJ: &str = "RETURN_VALUE";
+pub const ERROR_OBJ: &str = "ERROR";
+pub const FUNCTION_OBJ: &str = "FUNCTION";
+pub const COMPILED_FUNCTION_OBJ: &str = "COMPILED_FUNCTION";
+
+// Every value produced while running a Monkey program
+#[derive(Debug, Clone)]
+pub enum Object<'a> {
+    Integer(Integer),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+    Array(Rc<Vec<Object<'a>>>),
+    Hash(Rc<HashMap<HashKey, HashPair<'a>>>),
+    Null,
+    ReturnValue(Box<Object<'a>>), // Wraps the value of a `return` while it bubbles up
+    Error(String),
+    Function(Rc<Function<'a>>),
+    CompiledFunction(Rc<CompiledFunction>), // Only found in the constant pool of the bytecode
+    Closure(Rc<Closure<'a>>),               // Function value of the virtual machine
+    Builtin(Builtin),
+    HostFunction(Rc<HostFunction<'a>>), // Registered with Engine::register_fn
+}
+
+#[derive(Clone)]
+pub struct Function<'a> {
+    pub parameters: Vec<Identifier<'a>>,
+    pub body: BlockStatement<'a>,
+    pub env: Rc<RefCell<Environment<'a>>>, // The environment the function was defined in
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    pub num_locals: usize,
+    pub num_parameters: usize,
+    pub source: String, // The literal as function_source prints it
+}
+
+pub type BuiltinFunction = for<'a> fn(&[Object<'a>]) -> Object<'a>;
+
+// Function implemented in Rust, see the builtins module
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub func: BuiltinFunction,
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+pub type HostClosure<'a> = dyn Fn(&[Object<'a>]) -> Object<'a> + 'a;
+
+// Rust closure of the program embedding Monkey
+pub struct HostFunction<'a> {
+    pub name: String,
+    pub func: Box<HostClosure<'a>>,
+}
+
+impl fmt::Debug for HostFunction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HostFunction({})", self.name)
+    }
+}
+
+// Compiled function bundled with the values of the free variables it captured
+#[derive(Debug)]
+pub struct Closure<'a> {
+    pub func: Rc<CompiledFunction>,
+    pub free: Vec<Object<'a>>,
+}
+
+impl Object<'_> {
+    pub fn object_type(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => INTEGER_OBJ,
+            Object::Float(_) => FLOAT_OBJ,
+            Object::Boolean(_) => BOOLEAN_OBJ,
+            Object::String(_) => STRING_OBJ,
+            Object::Array(_) => ARRAY_OBJ,
+            Object::Hash(_) => HASH_OBJ,
+            Object::Null => NULL_OBJ,
+            Object::ReturnValue(_) => RETURN_VALUE_OBJ,
+            Object::Error(_) => ERROR_OBJ,
+            Object::Function(_) => FUNCTION_OBJ,
+            Object::CompiledFunction(_) => COMPILED_FUNCTION_OBJ,
+            Object::Closure(_) => FUNCTION_OBJ, // Functions are the same in both backends
+            Object::Builtin(_) | Object::HostFunction(_) => BUILTIN_OBJ,
+        }
+    }
+
+    pub fn inspect(&self) -> String {
+        match self {
+            Object::Integer(x) => x.to_string(),
+            // Debug keeps the fraction of whole numbers, 2.0 is not shown as 2
+            Object::Float(x) => format!("{:?}", x),
+            Object::Boolean(x) => x.to_string(),
+            Object::String(x) => x.clone(),
+            Object::Array(x) => {
+                let elements: Vec<String> = x.iter().map(|e| e.inspect()).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            Object::Hash(x) => {
+                let mut keys: Vec<&HashKey> = x.keys().collect();
+                keys.sort();
+                let pairs: Vec<String> = keys
+                    .iter()
+                    .map(|k| format!("{}: {}", x[k].key.inspect(), x[k].value.inspect()))
+                    .collect();
+                format!("{{{}}}", pairs.join(", "))
+            }
+            Object::Null => String::from("null"),
+            Object::ReturnValue(x) => x.inspect(),
+            Object::Error(x) => format!("ERROR: {}", x),
+            Object::Function(x) => x.inspect(),
+            Object::CompiledFunction(x) => x.source.clone(),
+            Object::Closure(x) => x.func.source.clone(),
+            Object::Builtin(_) | Object::HostFunction(_) => String::from("builtin function"),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+
+    // Only integers, booleans and strings can be used as keys of a hash. Floats can't, as
+    // values that print the same can be different numbers.
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            Object::Integer(x) => Some(HashKey::Integer(x.to_owned())),
+            Object::Boolean(x) => Some(HashKey::Boolean(*x)),
+            Object::String(x) => Some(HashKey::String(x.clone())),
+            _ => None,
+        }
+    }
+}
+
+// Identifies a hash key by its value, so equal strings built at different places find the
+// same entry
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Clone)]
+pub enum HashKey {
+    Integer(Integer),
+    Boolean(bool),
+    String(String),
+}
+
+// The original key is kept next to the value to print the hash
+#[derive(Debug, Clone)]
+pub struct HashPair<'a> {
+    pub key: Object<'a>,
+    pub value: Object<'a>,
+}
+
+impl Function<'_> {
+    pub fn inspect(&self) -> String {
+        function_source(&self.parameters, &self.body)
+    }
+}
+
+// How a function value prints, the same for the evaluator and the virtual machine
+pub fn function_source(parameters: &[Identifier], body: &BlockStatement) -> String {
+    let params: Vec<String> = parameters.iter().map(|p| p.string()).collect();
+    format!("fn({}) {{\n{}\n}}", params.join(", "), body.string())
+}
+
+// The captured environment may contain the function itself (recursion), so it is left out
+impl fmt::Debug for Function<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.inspect())
+    }
+}
@@ -1,8 +1,14 @@
 use crate::ast::*;
-use crate::lexer::Lexer;
+use crate::diagnostic::{Diagnostic, Label};
+use crate::lexer::{float_value, integer_value, string_value, Lexer};
 use crate::token::*;
+use std::collections::HashMap;
 
-#[derive(Ord, PartialOrd, Eq, PartialEq, Debug)]
+type PrefixParseFn<'a> = fn(&mut Parser<'a>) -> Option<Expression<'a>>;
+type InfixParseFn<'a> = fn(&mut Parser<'a>, Expression<'a>) -> Option<Expression<'a>>;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Ord, PartialOrd, Eq, PartialEq, Debug, Clone, Copy)]
 pub enum Precedence {
     LOWEST,
     EQUALS,      // ==
@@ -11,66 +17,465 @@ pub enum Precedence {
     PRODUCT,     // *
     PREFIX,      // -X or !X
     CALL,        // myFunction(X)
+    INDEX,       // array[index]
+}
+
+// Expressions that can be nested in each other, e.g. `((((1))))` is 5 levels deep. Parsing,
+// compiling and evaluating the deepest program stays inside the 2 MiB stack of a thread even in
+// a debug build, where a nested `if` takes about 13 KiB of it per level to parse.
+const MAX_NESTING: usize = 64;
+
+// Binding power of every token that can appear in infix position
+fn token_precedence(t: &TokenKind) -> Precedence {
+    match *t {
+        EQ | NOTEQ => Precedence::EQUALS,
+        LT | GT => Precedence::LESSGREATER,
+        PLUS | MINUS => Precedence::SUM,
+        SLASH | ASTERISK => Precedence::PRODUCT,
+        LPAREN => Precedence::CALL,
+        LBRACKET => Precedence::INDEX,
+        _ => Precedence::LOWEST,
+    }
 }
 
 pub struct Parser<'a> {
     l: Lexer<'a>,
     cur_token: Token<'a>,
     peek_token: Token<'a>,
-    errors: Vec<String>,
+    errors: Vec<Diagnostic>,
+    prefix_parse_fns: HashMap<TokenKind, PrefixParseFn<'a>>,
+    infix_parse_fns: HashMap<TokenKind, InfixParseFn<'a>>,
+    open_hashes: usize, // Hash literals being parsed, synchronize skips to their closing braces
+    depth: usize,       // Expressions being parsed that contain the current one
+    too_deep: bool,     // The outermost expression went past MAX_NESTING
 }
 
 impl<'a> Parser<'a> {
-    fn new(l: Lexer<'a>) -> Parser<'a> {
+    pub fn new(l: Lexer<'a>) -> Parser<'a> {
+        // Replaced by the first two tokens of the input below
+        let none = Token {
+            kind: EOF,
+            lexeme: "",
+            span: Span::default(),
+        };
         let mut p: Parser = Parser {
             l,
-            cur_token: Token::new(),
-            peek_token: Token::new(),
+            cur_token: none,
+            peek_token: none,
             errors: vec![],
+            prefix_parse_fns: HashMap::new(),
+            infix_parse_fns: HashMap::new(),
+            open_hashes: 0,
+            depth: 0,
+            too_deep: false,
         };
+
+        p.register_prefix(IDENT, Parser::parse_identifier);
+        p.register_prefix(INT, Parser::parse_integer_literal);
+        p.register_prefix(FLOAT, Parser::parse_float_literal);
+        p.register_prefix(STRING, Parser::parse_string_literal);
+        p.register_prefix(TRUE, Parser::parse_boolean);
+        p.register_prefix(FALSE, Parser::parse_boolean);
+        p.register_prefix(BANG, Parser::parse_prefix_expression);
+        p.register_prefix(MINUS, Parser::parse_prefix_expression);
+        p.register_prefix(LPAREN, Parser::parse_grouped_expression);
+        p.register_prefix(IF, Parser::parse_if_expression);
+        p.register_prefix(FUNCTION, Parser::parse_function_literal);
+        p.register_prefix(LBRACKET, Parser::parse_array_literal);
+        p.register_prefix(LBRACE, Parser::parse_hash_literal);
+
+        for t in [PLUS, MINUS, SLASH, ASTERISK, EQ, NOTEQ, LT, GT]
+            .iter()
+            .cloned()
+        {
+            p.register_infix(t, Parser::parse_infix_expression);
+        }
+        p.register_infix(LPAREN, Parser::parse_call_expression);
+        p.register_infix(LBRACKET, Parser::parse_index_expression);
+
         p.next_token();
         p.next_token();
         p
     }
 
-    fn errors(&self) -> &Vec<String> {
+    pub fn errors(&self) -> &Vec<Diagnostic> {
         &self.errors
     }
 
     fn next_token(&mut self) {
-        self.cur_token = self.peek_token.clone();
+        self.cur_token = self.peek_token;
         self.peek_token = self.l.next_token();
+        self.errors.append(&mut self.l.take_errors());
     }
 
     pub fn parse_program(&mut self) -> Program<'a> {
         let mut program = Program::new();
 
-        while self.cur_token.ttype != EOF {
-            let stmt = self.parse_statement();
-            if stmt.is_some() {
-                program.statements.push(stmt.unwrap());
+        while self.cur_token.kind != EOF {
+            if let Some(stmt) = self.parse_statement() {
+                program.statements.push(stmt);
+            } else {
+                self.synchronize();
             }
             self.next_token();
         }
         program
     }
 
-    fn prefix_fn(&mut self) -> Option<Expression<'a>> {
-        match self.cur_token.ttype {
-            TokenType::IDENT(_) => Some(self.parse_identifier()),
-            _ => None,
+    fn register_prefix(&mut self, t: TokenKind, f: PrefixParseFn<'a>) {
+        self.prefix_parse_fns.insert(t, f);
+    }
+
+    fn register_infix(&mut self, t: TokenKind, f: InfixParseFn<'a>) {
+        self.infix_parse_fns.insert(t, f);
+    }
+
+    fn parse_identifier(&mut self) -> Option<Expression<'a>> {
+        Some(Expression::Identifier(Identifier {
+            token: self.cur_token,
+            value: self.cur_token.lexeme.to_string(),
+        }))
+    }
+
+    fn parse_integer_literal(&mut self) -> Option<Expression<'a>> {
+        match integer_value(self.cur_token.lexeme) {
+            Ok(value) => Some(Expression::IntegerLiteral(IntegerLiteral {
+                token: self.cur_token,
+                value,
+            })),
+            Err(msg) => {
+                self.errors
+                    .push(Diagnostic::error("E0004", msg, self.cur_token.span));
+                None
+            }
+        }
+    }
+
+    fn parse_float_literal(&mut self) -> Option<Expression<'a>> {
+        match float_value(self.cur_token.lexeme) {
+            Ok(value) => Some(Expression::FloatLiteral(FloatLiteral {
+                token: self.cur_token,
+                value,
+            })),
+            Err(msg) => {
+                self.errors
+                    .push(Diagnostic::error("E0004", msg, self.cur_token.span));
+                None
+            }
+        }
+    }
+
+    fn parse_string_literal(&mut self) -> Option<Expression<'a>> {
+        Some(Expression::StringLiteral(StringLiteral {
+            token: self.cur_token,
+            value: string_value(self.cur_token.lexeme),
+        }))
+    }
+
+    fn parse_boolean(&mut self) -> Option<Expression<'a>> {
+        Some(Expression::Boolean(Boolean {
+            token: self.cur_token,
+            value: self.cur_token_is(TRUE),
+        }))
+    }
+
+    fn parse_prefix_expression(&mut self) -> Option<Expression<'a>> {
+        let token = self.cur_token;
+        let operator = self.cur_token.lexeme.to_string();
+
+        self.next_token();
+
+        let right = self.parse_expression(Precedence::PREFIX)?;
+        Some(Expression::PrefixExpression(PrefixExpression {
+            span: token.span.to(&right.span()),
+            token,
+            operator,
+            right: Box::new(right),
+        }))
+    }
+
+    fn parse_infix_expression(&mut self, left: Expression<'a>) -> Option<Expression<'a>> {
+        let token = self.cur_token;
+        let operator = self.cur_token.lexeme.to_string();
+        let precedence = self.cur_precedence();
+
+        self.next_token();
+
+        let right = self.parse_expression(precedence)?;
+        Some(Expression::InfixExpression(InfixExpression {
+            span: left.span().to(&right.span()),
+            token,
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        }))
+    }
+
+    fn parse_grouped_expression(&mut self) -> Option<Expression<'a>> {
+        let open = self.cur_token.span;
+        self.next_token();
+
+        let exp = self.parse_expression(Precedence::LOWEST);
+        // Past the nesting limit none of the closing parens is reached, one error is enough
+        if exp.is_none() && self.too_deep {
+            return None;
+        }
+        if !self.expect_closing(RPAREN, open) {
+            return None;
+        }
+        exp
+    }
+
+    fn parse_if_expression(&mut self) -> Option<Expression<'a>> {
+        let token = self.cur_token;
+
+        if !self.expect_peek(LPAREN) {
+            return None;
+        }
+        let open = self.cur_token.span;
+
+        self.next_token();
+        let condition = self.parse_expression(Precedence::LOWEST)?;
+
+        if !self.expect_closing(RPAREN, open) {
+            return None;
+        }
+
+        if !self.expect_peek(LBRACE) {
+            return None;
+        }
+
+        let consequence = self.parse_block_statement();
+
+        let mut alternative = None;
+        if self.peek_token_is(&ELSE) {
+            self.next_token();
+
+            if !self.expect_peek(LBRACE) {
+                return None;
+            }
+            alternative = Some(self.parse_block_statement());
+        }
+
+        Some(Expression::IfExpression(IfExpression {
+            span: token.span.to(&self.cur_token.span),
+            token,
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+        }))
+    }
+
+    fn parse_function_literal(&mut self) -> Option<Expression<'a>> {
+        let token = self.cur_token;
+
+        if !self.expect_peek(LPAREN) {
+            return None;
+        }
+
+        let parameters = self.parse_function_parameters()?;
+
+        if !self.expect_peek(LBRACE) {
+            return None;
+        }
+
+        let body = self.parse_block_statement();
+        Some(Expression::FunctionLiteral(FunctionLiteral {
+            span: token.span.to(&self.cur_token.span),
+            token,
+            parameters,
+            body,
+        }))
+    }
+
+    fn parse_function_parameters(&mut self) -> Option<Vec<Identifier<'a>>> {
+        let mut identifiers = vec![];
+        let open = self.cur_token.span;
+
+        if self.peek_token_is(&RPAREN) {
+            self.next_token();
+            return Some(identifiers);
+        }
+
+        if !self.expect_peek(IDENT) {
+            return None;
+        }
+        identifiers.push(Identifier {
+            token: self.cur_token,
+            value: self.cur_token.lexeme.to_string(),
+        });
+
+        while self.peek_token_is(&COMMA) {
+            self.next_token();
+            if !self.expect_peek(IDENT) {
+                return None;
+            }
+            identifiers.push(Identifier {
+                token: self.cur_token,
+                value: self.cur_token.lexeme.to_string(),
+            });
+        }
+
+        if !self.expect_closing(RPAREN, open) {
+            return None;
+        }
+        Some(identifiers)
+    }
+
+    fn parse_call_expression(&mut self, function: Expression<'a>) -> Option<Expression<'a>> {
+        let token = self.cur_token;
+        let arguments = self.parse_expression_list(RPAREN)?;
+        Some(Expression::CallExpression(CallExpression {
+            span: function.span().to(&self.cur_token.span),
+            token,
+            function: Box::new(function),
+            arguments,
+        }))
+    }
+
+    // Comma separated expressions up to the `end` token, e.g. call arguments
+    fn parse_expression_list(&mut self, end: TokenKind) -> Option<Vec<Expression<'a>>> {
+        let mut list = vec![];
+        let open = self.cur_token.span;
+
+        if self.peek_token_is(&end) {
+            self.next_token();
+            return Some(list);
+        }
+
+        self.next_token();
+        list.push(self.parse_expression(Precedence::LOWEST)?);
+
+        while self.peek_token_is(&COMMA) {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(Precedence::LOWEST)?);
+        }
+
+        if !self.expect_closing(end, open) {
+            return None;
+        }
+        Some(list)
+    }
+
+    fn parse_array_literal(&mut self) -> Option<Expression<'a>> {
+        let token = self.cur_token;
+        let elements = self.parse_expression_list(RBRACKET)?;
+        Some(Expression::ArrayLiteral(ArrayLiteral {
+            span: token.span.to(&self.cur_token.span),
+            token,
+            elements,
+        }))
+    }
+
+    // Blocks are only parsed where a statement list is expected (after `if`, `else` and `fn`),
+    // so a `{` in the place of an expression always starts a hash literal
+    fn parse_hash_literal(&mut self) -> Option<Expression<'a>> {
+        let token = self.cur_token;
+        if self.looks_like_block() {
+            self.errors.push(
+                Diagnostic::error(
+                    "E0003",
+                    String::from("expected an expression, got a block instead"),
+                    token.span,
+                )
+                .with_label(String::from("blocks only follow `if`, `else` and `fn`"))
+                .with_help(String::from(
+                    "a `{` in the place of an expression starts a hash literal like `{\"a\": 1}`",
+                )),
+            );
+            return None;
+        }
+
+        let mut pairs = vec![];
+        self.open_hashes += 1;
+
+        while !self.peek_token_is(&RBRACE) {
+            self.next_token();
+            let key = self.parse_expression(Precedence::LOWEST)?;
+            if !self.expect_peek(COLON) {
+                return None;
+            }
+
+            self.next_token();
+            let value = self.parse_expression(Precedence::LOWEST)?;
+            pairs.push((key, value));
+
+            if self.peek_token_is(&EOF) {
+                break;
+            }
+            if !self.peek_token_is(&RBRACE) && !self.expect_peek(COMMA) {
+                return None;
+            }
+        }
+
+        if !self.expect_closing(RBRACE, token.span) {
+            return None;
+        }
+        self.open_hashes -= 1;
+        Some(Expression::HashLiteral(HashLiteral {
+            span: token.span.to(&self.cur_token.span),
+            token,
+            pairs,
+        }))
+    }
+
+    fn parse_index_expression(&mut self, left: Expression<'a>) -> Option<Expression<'a>> {
+        let token = self.cur_token;
+        let open = token.span;
+
+        self.next_token();
+        let index = self.parse_expression(Precedence::LOWEST)?;
+        if !self.expect_closing(RBRACKET, open) {
+            return None;
         }
+
+        Some(Expression::IndexExpression(IndexExpression {
+            span: left.span().to(&self.cur_token.span),
+            token,
+            left: Box::new(left),
+            index: Box::new(index),
+        }))
     }
 
-    fn parse_identifier(&self) -> Expression<'a> {
-        Expression::Identifier(Identifier {
-            token: self.cur_token.clone(),
-            value: self.cur_token.literal.clone(),
-        })
+    fn parse_block_statement(&mut self) -> BlockStatement<'a> {
+        let mut block = BlockStatement {
+            token: self.cur_token,
+            statements: vec![],
+            span: self.cur_token.span,
+        };
+        // A block inside a hash literal recovers from its errors on its own
+        let open_hashes = std::mem::take(&mut self.open_hashes);
+
+        self.next_token();
+
+        while !self.cur_token_is(RBRACE) && !self.cur_token_is(EOF) {
+            match self.parse_statement() {
+                Some(stmt) => block.statements.push(stmt),
+                None => {
+                    if self.synchronize() {
+                        break;
+                    }
+                }
+            }
+            self.next_token();
+        }
+
+        if self.cur_token_is(EOF) {
+            let msg = format!("expected {}, got {} instead", RBRACE, EOF);
+            self.errors.push(
+                Diagnostic::error("E0002", msg, self.cur_token.span)
+                    .with_label(format!("expected {}", RBRACE))
+                    .with_secondary(block.token.span, String::from("unclosed delimiter")),
+            );
+        }
+        block.span = block.span.to(&self.cur_token.span);
+        self.open_hashes = open_hashes;
+        block
     }
 
     fn parse_statement(&mut self) -> Option<Statement<'a>> {
-        match self.cur_token.ttype {
+        match self.cur_token.kind {
             LET => self.parse_let_statement(),
             RETURN => self.parse_return_statement(),
             _ => self.parse_expression_statement(),
@@ -78,80 +483,217 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_let_statement(&mut self) -> Option<Statement<'a>> {
-        let mut stmt = LetStatement {
-            token: self.cur_token.clone(),
-            name: None,
-            value: None,
-        };
+        let token = self.cur_token;
 
         if !self.expect_peek(IDENT) {
             return None;
         }
 
-        stmt.name = Some(Identifier {
-            token: self.cur_token.clone(),
-            value: self.cur_token.literal.clone(),
-        });
+        let name = Identifier {
+            token: self.cur_token,
+            value: self.cur_token.lexeme.to_string(),
+        };
 
         if !self.expect_peek(ASSIGN) {
+            if let Some(err) = self.errors.last_mut() {
+                err.help.push(String::from(
+                    "a binding looks like `let <name> = <expression>;`",
+                ));
+            }
             return None;
         }
-        // TODO: We're skipping the expressions until we encounter a semicolon
-        while self.cur_token_is(SEMICOLON) {
+
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::LOWEST)?;
+
+        if self.peek_token_is(&SEMICOLON) {
             self.next_token();
         }
-        Some(Statement::LetStatement(stmt))
+        Some(Statement::LetStatement(LetStatement {
+            span: token.span.to(&self.cur_token.span),
+            token,
+            name,
+            value,
+        }))
     }
 
     fn parse_return_statement(&mut self) -> Option<Statement<'a>> {
-        let stmt = ReturnStatement {
-            token: self.cur_token.clone(),
-            return_value: None,
-        };
+        let token = self.cur_token;
+
         self.next_token();
 
-        // TODO: We're skipping the expressions until we encounter a semicolon
+        let return_value = self.parse_expression(Precedence::LOWEST)?;
 
-        while self.cur_token_is(SEMICOLON) {
+        if self.peek_token_is(&SEMICOLON) {
             self.next_token();
         }
-        Some(Statement::ReturnStatement(stmt))
+        Some(Statement::ReturnStatement(ReturnStatement {
+            span: token.span.to(&self.cur_token.span),
+            token,
+            return_value,
+        }))
     }
 
     fn parse_expression_statement(&mut self) -> Option<Statement<'a>> {
-        let stmt = ExpressionStatement {
-            token: self.cur_token.clone(),
-            expression: self.parse_expression(Precedence::LOWEST).unwrap(),
+        let token = self.cur_token;
+        let mut stmt = ExpressionStatement {
+            expression: self.parse_expression(Precedence::LOWEST)?,
+            span: token.span,
+            token,
         };
 
         if self.peek_token_is(&SEMICOLON) {
             self.next_token();
         }
+        stmt.span = stmt.token.span.to(&self.cur_token.span);
         Some(Statement::ExpressionStatement(stmt))
     }
 
-    fn parse_expression(&mut self, _precedence: Precedence) -> Option<Expression<'a>> {
-        let prefix = self.prefix_fn();
-        prefix
+    // Every nested expression is parsed by a recursive call, and later evaluated and compiled
+    // by one. The nesting is limited so that none of them can run out of stack.
+    fn parse_expression(&mut self, precedence: Precedence) -> Option<Expression<'a>> {
+        if self.depth >= MAX_NESTING {
+            let msg = format!(
+                "expression nested too deeply, the limit is {} levels",
+                MAX_NESTING
+            );
+            self.errors.push(
+                Diagnostic::error("E0008", msg, self.cur_token.span)
+                    .with_label(String::from("nested too deeply")),
+            );
+            self.too_deep = true;
+            return None;
+        }
+
+        self.depth += 1;
+        let expression = self.parse_nested_expression(precedence);
+        self.depth -= 1;
+        if self.depth == 0 {
+            self.too_deep = false;
+        }
+        expression
+    }
+
+    // Top-down operator precedence (Pratt) parsing: parse the prefix part, then keep folding
+    // it into infix expressions for as long as the next operator binds tighter than `precedence`
+    fn parse_nested_expression(&mut self, precedence: Precedence) -> Option<Expression<'a>> {
+        let prefix = match self.prefix_parse_fns.get(&self.cur_token.kind) {
+            Some(f) => *f,
+            None => {
+                self.no_prefix_parse_fn_error();
+                return None;
+            }
+        };
+        let mut left_exp = prefix(self)?;
+
+        while !self.peek_token_is(&SEMICOLON) && precedence < self.peek_precedence() {
+            let infix = match self.infix_parse_fns.get(&self.peek_token.kind) {
+                Some(f) => *f,
+                None => return Some(left_exp),
+            };
+
+            self.next_token();
+            left_exp = infix(self, left_exp)?;
+        }
+        Some(left_exp)
+    }
+
+    // Skip the rest of a statement that failed to parse. Stops on the `;` ending it, or before
+    // the `let`, `return` or `}` that follows it, so the caller can carry on with the next
+    // statement and report every error of the input in one go. Returns true when the statement
+    // failed on the `}` closing the enclosing block, which is left for the block to end on.
+    fn synchronize(&mut self) -> bool {
+        // Braces opened before or while skipping, their contents are skipped as well
+        let mut depth = std::mem::take(&mut self.open_hashes);
+
+        loop {
+            match self.cur_token.kind {
+                EOF => return false,
+                LBRACE => depth += 1,
+                RBRACE if depth > 0 => depth -= 1,
+                RBRACE => return true,
+                SEMICOLON if depth == 0 => return false,
+                _ => (),
+            }
+
+            if depth == 0
+                && (self.peek_token_is(&LET)
+                    || self.peek_token_is(&RETURN)
+                    || self.peek_token_is(&RBRACE)
+                    || self.peek_token_is(&EOF))
+            {
+                return false;
+            }
+            self.next_token();
+        }
+    }
+
+    fn peek_precedence(&self) -> Precedence {
+        token_precedence(&self.peek_token.kind)
+    }
+
+    fn cur_precedence(&self) -> Precedence {
+        token_precedence(&self.cur_token.kind)
+    }
+
+    fn no_prefix_parse_fn_error(&mut self) {
+        // The lexer has already reported the character it did not recognize
+        if self.cur_token_is(ILLEGAL) {
+            return;
+        }
+
+        let msg = format!(
+            "expected an expression, got {} instead",
+            self.cur_token.kind
+        );
+        self.errors.push(
+            Diagnostic::error("E0003", msg, self.cur_token.span)
+                .with_label(String::from("expected an expression")),
+        );
+    }
+
+    fn cur_token_is(&self, t: TokenKind) -> bool {
+        self.cur_token.kind == t
+    }
+
+    fn peek_token_is(&self, t: &TokenKind) -> bool {
+        self.peek_token.kind == *t
     }
 
-    fn cur_token_is(&self, t: TokenType) -> bool {
-        self.cur_token.ttype == t
+    // Token `n` places after cur_token, peek_nth(1) is peek_token. For the constructs that can't
+    // be told apart by the next token alone.
+    fn peek_nth(&mut self, n: usize) -> Token<'a> {
+        match n {
+            0 => self.cur_token,
+            1 => self.peek_token,
+            _ => self.l.peek(n - 2),
+        }
     }
 
-    fn peek_token_is(&self, t: &TokenType) -> bool {
-        self.peek_token.ttype == *t
+    // Whether the `{` at cur_token opens statements rather than hash pairs: it is followed by
+    // `let` or `return`, or by a single token and then `;` or `}`, where a key needs a `:`. An
+    // empty hash `{}` is a key on its own.
+    fn looks_like_block(&mut self) -> bool {
+        match self.peek_nth(1).kind {
+            LET | RETURN => true,
+            LBRACE | RBRACE | EOF => false,
+            _ => matches!(self.peek_nth(2).kind, SEMICOLON | RBRACE),
+        }
     }
 
-    fn peek_error(&mut self, t: &TokenType) {
+    fn peek_error(&mut self, t: &TokenKind) {
         let msg = format!(
-            "expected next token to be {:?}, got {:?} instead",
-            t, self.peek_token.ttype
+            "expected next token to be {}, got {} instead",
+            t, self.peek_token.kind
+        );
+        self.errors.push(
+            Diagnostic::error("E0002", msg, self.peek_token.span)
+                .with_label(format!("expected {}", t)),
         );
-        self.errors.push(msg);
     }
 
-    fn expect_peek(&mut self, t: TokenType) -> bool {
+    fn expect_peek(&mut self, t: TokenKind) -> bool {
         if self.peek_token_is(&t) {
             self.next_token();
             true
@@ -160,17 +702,36 @@ impl<'a> Parser<'a> {
             false
         }
     }
+
+    // Like expect_peek, but on failure also points at the delimiter that was left open
+    fn expect_closing(&mut self, t: TokenKind, open: Span) -> bool {
+        if self.expect_peek(t) {
+            return true;
+        }
+
+        if let Some(err) = self.errors.last_mut() {
+            err.secondary.push(Label {
+                span: open,
+                message: String::from("unclosed delimiter"),
+            });
+        }
+        false
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::diagnostic::RenderMode;
+    use crate::integer;
+
     #[test]
     fn test_let_statements() {
         let input = r"let x = 5;
-                          let y = 10;
-                          let foobar = 838383;";
+                          let y = true;
+                          let foobar = y;
+                          let z = 1 + 2 * 3;";
         let l = Lexer::new(input);
         let mut p = Parser::new(l);
         let program = p.parse_program();
@@ -178,56 +739,87 @@ mod tests {
 
         assert_eq!(
             program.statements.len(),
-            3,
-            "Program.statements does not contain 3 statements got={}",
+            4,
+            "Program.statements does not contain 4 statements got={}",
             program.statements.len()
         );
 
         struct ExpectedIdentifier<'a> {
             value: &'a str,
+            expected_value: &'a str,
         }
 
-        let tests = vec![
-            ExpectedIdentifier { value: "x" },
-            ExpectedIdentifier { value: "y" },
-            ExpectedIdentifier { value: "foobar" },
+        let tests = [
+            ExpectedIdentifier {
+                value: "x",
+                expected_value: "5",
+            },
+            ExpectedIdentifier {
+                value: "y",
+                expected_value: "true",
+            },
+            ExpectedIdentifier {
+                value: "foobar",
+                expected_value: "y",
+            },
+            ExpectedIdentifier {
+                value: "z",
+                expected_value: "(1 + (2 * 3))",
+            },
         ];
 
         for (i, tt) in tests.iter().enumerate() {
             let stmt = match &program.statements[i] {
                 Statement::LetStatement(x) => x,
-                _ => panic!("Expected letStatement, found {:?}", &program.statements[1]),
+                _ => panic!("Expected letStatement, found {:?}", &program.statements[i]),
             };
 
             assert_eq!(
-                stmt.token.literal,
+                stmt.token.lexeme,
                 String::from("let"),
-                "s.token.literal not 'let'. got={}",
-                stmt.token.literal
+                "s.token.lexeme not 'let'. got={}",
+                stmt.token.lexeme
             );
 
             assert_eq!(
-                stmt.name.as_ref().unwrap().value.to_string(),
-                tt.value,
+                stmt.name.value, tt.value,
                 "stmt.Name.Value not'{}'.got={}",
-                tt.value,
-                stmt.name.as_ref().unwrap().value.to_string()
+                tt.value, stmt.name.value
             );
 
             assert_eq!(
-                stmt.name.as_ref().unwrap().token.literal.to_string(),
-                tt.value,
+                stmt.name.token.lexeme, tt.value,
                 "s.name not '{}'. got={}",
-                tt.value,
-                stmt.name.as_ref().unwrap().token.literal.to_string(),
-            )
+                tt.value, stmt.name.token.lexeme,
+            );
+
+            assert_eq!(
+                stmt.value.string(),
+                tt.expected_value,
+                "stmt.Value not '{}'. got={}",
+                tt.expected_value,
+                stmt.value.string()
+            );
+        }
+
+        match &program.statements[0] {
+            Statement::LetStatement(x) => test_integer_literal(&x.value, 5),
+            stmt => panic!("Expected letStatement, found {:?}", stmt),
+        }
+        match &program.statements[1] {
+            Statement::LetStatement(x) => test_boolean(&x.value, true),
+            stmt => panic!("Expected letStatement, found {:?}", stmt),
+        }
+        match &program.statements[2] {
+            Statement::LetStatement(x) => test_identifier(&x.value, "y"),
+            stmt => panic!("Expected letStatement, found {:?}", stmt),
         }
     }
 
     fn check_parse_errors(p: Parser) {
         let errors = p.errors();
 
-        if errors.len() == 0 {
+        if errors.is_empty() {
             return;
         }
 
@@ -241,7 +833,8 @@ mod tests {
     fn test_return_statements() {
         let input = r"
                          return 5;
-                         return 10;
+                         return true;
+                         return foobar;
                          return 993322
                          ";
 
@@ -252,21 +845,30 @@ mod tests {
 
         assert_eq!(
             program.statements.len(),
-            3,
-            "Program.statements does not contain 3 statements got={}",
+            4,
+            "Program.statements does not contain 4 statements got={}",
             program.statements.len()
         );
 
-        for stmt in &program.statements {
+        let tests = [
+            Literal::Int(5),
+            Literal::Bool(true),
+            Literal::Ident("foobar"),
+            Literal::Int(993322),
+        ];
+
+        for (stmt, expected) in program.statements.iter().zip(tests.iter()) {
             match stmt {
-                Statement::LetStatement(x) => panic!("stmt not ast.ReturnStatement, got={:?}", x),
-                Statement::ReturnStatement(x) => assert_eq!(
-                    x.token.literal,
-                    String::from("return"),
-                    "returnStmt.TokenLiteral not 'return', got {}",
-                    x.token.literal
-                ),
-                _ => (),
+                Statement::ReturnStatement(x) => {
+                    assert_eq!(
+                        x.token.lexeme,
+                        String::from("return"),
+                        "returnStmt.TokenLiteral not 'return', got {}",
+                        x.token.lexeme
+                    );
+                    test_literal_expression(&x.return_value, expected);
+                }
+                _ => panic!("stmt not ast.ReturnStatement, got={:?}", stmt),
             }
         }
     }
@@ -287,17 +889,17 @@ mod tests {
             program.statements.len()
         );
 
-        let stmt = program.statements.iter().nth(0).unwrap();
+        let stmt = program.statements.first().unwrap();
 
         match stmt {
             Statement::ExpressionStatement(x) => match &x.expression {
                 Expression::Identifier(y) => {
-                    if y.value != String::from("foobar") {
+                    if y.value != "foobar" {
                         panic!("ident.Value not {}. got={}", "foobar", y.value)
-                    } else if y.token.literal != String::from("foobar") {
+                    } else if y.token.lexeme != "foobar" {
                         panic!(
                             "ident.TokenLiteral not {}. got={}",
-                            "foobar", y.token.literal
+                            "foobar", y.token.lexeme
                         )
                     }
                 }
@@ -309,4 +911,1079 @@ mod tests {
             ),
         };
     }
+
+    #[test]
+    fn integer_literal_expression() {
+        let input = "5;";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        check_parse_errors(p);
+
+        assert_eq!(
+            program.statements.len(),
+            1,
+            "Program.statements does not contain 1 statements got={}",
+            program.statements.len()
+        );
+
+        match &program.statements[0] {
+            Statement::ExpressionStatement(x) => test_integer_literal(&x.expression, 5),
+            stmt => panic!(
+                "program.Statements[0] is not ast.ExpressionStatement. got={:?}",
+                stmt
+            ),
+        };
+    }
+
+    #[test]
+    fn float_literal_expression() {
+        let tests = vec![
+            ("1.5;", 1.5),
+            ("0.25", 0.25),
+            ("1e3", 1000.0),
+            ("2.5e-3", 0.0025),
+        ];
+
+        for (input, expected) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            let program = p.parse_program();
+            check_parse_errors(p);
+
+            match &program.statements[0] {
+                Statement::ExpressionStatement(ExpressionStatement {
+                    expression: Expression::FloatLiteral(x),
+                    ..
+                }) => {
+                    assert_eq!(x.value, expected, "literal.value not {}", expected);
+                    assert_eq!(x.token.lexeme, input.trim_end_matches(';'));
+                }
+                stmt => panic!("exp not *ast.FloatLiteral. got={:?}", stmt),
+            };
+        }
+    }
+
+    #[test]
+    fn string_literal_expression() {
+        let tests = vec![
+            (r#""hello world";"#, "hello world", r#""hello world""#),
+            (r#""a\tb\n""#, "a\tb\n", r#""a\tb\n""#),
+            (
+                r#""say \"hi\" \\o/""#,
+                "say \"hi\" \\o/",
+                r#""say \"hi\" \\o/""#,
+            ),
+            (r#""\u{e9}t\u{e9}""#, "été", r#""été""#),
+        ];
+
+        for (input, expected, string) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            let program = p.parse_program();
+            check_parse_errors(p);
+
+            match &program.statements[0] {
+                Statement::ExpressionStatement(ExpressionStatement {
+                    expression: Expression::StringLiteral(x),
+                    ..
+                }) => assert_eq!(
+                    x.value, expected,
+                    "literal.value not {}. got={}",
+                    expected, x.value
+                ),
+                stmt => panic!("exp not ast.StringLiteral. got={:?}", stmt),
+            };
+            assert_eq!(program.string(), string);
+        }
+    }
+
+    #[test]
+    fn boolean_expression() {
+        let tests = vec![("true;", true), ("false;", false)];
+
+        for (input, expected) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            let program = p.parse_program();
+            check_parse_errors(p);
+
+            assert_eq!(program.statements.len(), 1);
+            match &program.statements[0] {
+                Statement::ExpressionStatement(x) => test_boolean(&x.expression, expected),
+                stmt => panic!(
+                    "program.Statements[0] is not ast.ExpressionStatement. got={:?}",
+                    stmt
+                ),
+            };
+        }
+    }
+
+    #[test]
+    fn parsing_prefix_expressions() {
+        let tests = vec![
+            ("!5;", "!", Literal::Int(5)),
+            ("-15;", "-", Literal::Int(15)),
+            ("!true;", "!", Literal::Bool(true)),
+            ("!false;", "!", Literal::Bool(false)),
+        ];
+
+        for (input, operator, value) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            let program = p.parse_program();
+            check_parse_errors(p);
+
+            assert_eq!(
+                program.statements.len(),
+                1,
+                "Program.statements does not contain 1 statements got={}",
+                program.statements.len()
+            );
+
+            match &program.statements[0] {
+                Statement::ExpressionStatement(x) => match &x.expression {
+                    Expression::PrefixExpression(exp) => {
+                        assert_eq!(
+                            exp.operator, operator,
+                            "exp.Operator is not '{}'. got={}",
+                            operator, exp.operator
+                        );
+                        test_literal_expression(&exp.right, &value);
+                    }
+                    _ => panic!("stmt is not ast.PrefixExpression. got={:?}", x.expression),
+                },
+                stmt => panic!(
+                    "program.Statements[0] is not ast.ExpressionStatement. got={:?}",
+                    stmt
+                ),
+            };
+        }
+    }
+
+    #[test]
+    fn parsing_infix_expressions() {
+        let tests = vec![
+            ("5 + 5;", Literal::Int(5), "+", Literal::Int(5)),
+            ("5 - 5;", Literal::Int(5), "-", Literal::Int(5)),
+            ("5 * 5;", Literal::Int(5), "*", Literal::Int(5)),
+            ("5 / 5;", Literal::Int(5), "/", Literal::Int(5)),
+            ("5 > 5;", Literal::Int(5), ">", Literal::Int(5)),
+            ("5 < 5;", Literal::Int(5), "<", Literal::Int(5)),
+            ("5 == 5;", Literal::Int(5), "==", Literal::Int(5)),
+            ("5 != 5;", Literal::Int(5), "!=", Literal::Int(5)),
+            (
+                "true == true",
+                Literal::Bool(true),
+                "==",
+                Literal::Bool(true),
+            ),
+            (
+                "true != false",
+                Literal::Bool(true),
+                "!=",
+                Literal::Bool(false),
+            ),
+            (
+                "false == false",
+                Literal::Bool(false),
+                "==",
+                Literal::Bool(false),
+            ),
+        ];
+
+        for (input, left, operator, right) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            let program = p.parse_program();
+            check_parse_errors(p);
+
+            assert_eq!(
+                program.statements.len(),
+                1,
+                "Program.statements does not contain 1 statements got={}",
+                program.statements.len()
+            );
+
+            match &program.statements[0] {
+                Statement::ExpressionStatement(x) => {
+                    test_infix_expression(&x.expression, &left, operator, &right)
+                }
+                stmt => panic!(
+                    "program.Statements[0] is not ast.ExpressionStatement. got={:?}",
+                    stmt
+                ),
+            };
+        }
+    }
+
+    #[test]
+    fn operator_precedence_parsing() {
+        let tests = vec![
+            ("-a * b", "((-a) * b)"),
+            ("-1.5 * 2", "((-1.5) * 2)"),
+            ("1 + 2.5e3 / x", "(1 + (2.5e3 / x))"),
+            ("!-a", "(!(-a))"),
+            ("a + b + c", "((a + b) + c)"),
+            ("a + b - c", "((a + b) - c)"),
+            ("a * b * c", "((a * b) * c)"),
+            ("a * b / c", "((a * b) / c)"),
+            ("a + b / c", "(a + (b / c))"),
+            ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
+            ("3 + 4; -5 * 5", "(3 + 4)((-5) * 5)"),
+            ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
+            ("5 < 4 != 3 > 4", "((5 < 4) != (3 > 4))"),
+            (
+                "3 + 4 * 5 == 3 * 1 + 4 * 5",
+                "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))",
+            ),
+            ("-a * b + c == d", "((((-a) * b) + c) == d)"),
+            ("true", "true"),
+            ("false", "false"),
+            ("3 > 5 == false", "((3 > 5) == false)"),
+            ("3 < 5 == true", "((3 < 5) == true)"),
+            ("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4)"),
+            ("(5 + 5) * 2", "((5 + 5) * 2)"),
+            ("2 / (5 + 5)", "(2 / (5 + 5))"),
+            ("-(5 + 5)", "(-(5 + 5))"),
+            ("!(true == true)", "(!(true == true))"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            (
+                "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
+                "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
+            ),
+            (
+                "add(a + b + c * d / f + g)",
+                "add((((a + b) + ((c * d) / f)) + g))",
+            ),
+            (
+                "a * [1, 2, 3, 4][b * c] * d",
+                "((a * ([1, 2, 3, 4][(b * c)])) * d)",
+            ),
+            (
+                "add(a * b[2], b[1], 2 * [1, 2][1])",
+                "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
+            ),
+            ("-a[0]", "(-(a[0]))"),
+            ("f(x)[0][1]", "((f(x)[0])[1])"),
+        ];
+
+        for (input, expected) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            let program = p.parse_program();
+            check_parse_errors(p);
+
+            assert_eq!(
+                program.string(),
+                expected,
+                "expected={}, got={}",
+                expected,
+                program.string()
+            );
+        }
+    }
+
+    #[test]
+    fn if_expression() {
+        let input = "if (x < y) { x }";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        check_parse_errors(p);
+
+        assert_eq!(
+            program.statements.len(),
+            1,
+            "Program.statements does not contain 1 statements got={}",
+            program.statements.len()
+        );
+
+        match &program.statements[0] {
+            Statement::ExpressionStatement(x) => match &x.expression {
+                Expression::IfExpression(exp) => {
+                    test_infix_expression(
+                        &exp.condition,
+                        &Literal::Ident("x"),
+                        "<",
+                        &Literal::Ident("y"),
+                    );
+                    assert_eq!(
+                        exp.consequence.statements.len(),
+                        1,
+                        "consequence is not 1 statements. got={}",
+                        exp.consequence.statements.len()
+                    );
+                    match &exp.consequence.statements[0] {
+                        Statement::ExpressionStatement(c) => test_identifier(&c.expression, "x"),
+                        stmt => panic!(
+                            "Statements[0] is not ast.ExpressionStatement. got={:?}",
+                            stmt
+                        ),
+                    }
+                    assert!(
+                        exp.alternative.is_none(),
+                        "exp.Alternative was not nil. got={:?}",
+                        exp.alternative
+                    );
+                }
+                _ => panic!("exp is not ast.IfExpression. got={:?}", x.expression),
+            },
+            stmt => panic!(
+                "program.Statements[0] is not ast.ExpressionStatement. got={:?}",
+                stmt
+            ),
+        };
+    }
+
+    #[test]
+    fn if_else_expression() {
+        let input = "if (x < y) { x } else { y }";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        check_parse_errors(p);
+
+        assert_eq!(program.string(), "if(x < y) xelse y");
+
+        match &program.statements[0] {
+            Statement::ExpressionStatement(x) => match &x.expression {
+                Expression::IfExpression(exp) => {
+                    let alternative = exp.alternative.as_ref().unwrap();
+                    assert_eq!(
+                        alternative.statements.len(),
+                        1,
+                        "alternative is not 1 statements. got={}",
+                        alternative.statements.len()
+                    );
+                    match &alternative.statements[0] {
+                        Statement::ExpressionStatement(a) => test_identifier(&a.expression, "y"),
+                        stmt => panic!(
+                            "Statements[0] is not ast.ExpressionStatement. got={:?}",
+                            stmt
+                        ),
+                    }
+                }
+                _ => panic!("exp is not ast.IfExpression. got={:?}", x.expression),
+            },
+            stmt => panic!(
+                "program.Statements[0] is not ast.ExpressionStatement. got={:?}",
+                stmt
+            ),
+        };
+    }
+
+    #[test]
+    fn function_literal_parsing() {
+        let input = "fn(x, y) { x + y; }";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        check_parse_errors(p);
+
+        assert_eq!(program.string(), "fn(x, y) (x + y)");
+
+        match &program.statements[0] {
+            Statement::ExpressionStatement(x) => match &x.expression {
+                Expression::FunctionLiteral(function) => {
+                    assert_eq!(
+                        function.parameters.len(),
+                        2,
+                        "function literal parameters wrong. want 2, got={}",
+                        function.parameters.len()
+                    );
+                    assert_eq!(function.parameters[0].value, "x");
+                    assert_eq!(function.parameters[1].value, "y");
+                    assert_eq!(
+                        function.body.statements.len(),
+                        1,
+                        "function.Body.Statements has not 1 statements. got={}",
+                        function.body.statements.len()
+                    );
+                    match &function.body.statements[0] {
+                        Statement::ExpressionStatement(b) => test_infix_expression(
+                            &b.expression,
+                            &Literal::Ident("x"),
+                            "+",
+                            &Literal::Ident("y"),
+                        ),
+                        stmt => panic!(
+                            "function body stmt is not ast.ExpressionStatement. got={:?}",
+                            stmt
+                        ),
+                    }
+                }
+                _ => panic!("exp is not ast.FunctionLiteral. got={:?}", x.expression),
+            },
+            stmt => panic!(
+                "program.Statements[0] is not ast.ExpressionStatement. got={:?}",
+                stmt
+            ),
+        };
+    }
+
+    #[test]
+    fn function_parameter_parsing() {
+        let tests = vec![
+            ("fn() {};", vec![]),
+            ("fn(x) {};", vec!["x"]),
+            ("fn(x, y, z) {};", vec!["x", "y", "z"]),
+        ];
+
+        for (input, expected_params) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            let program = p.parse_program();
+            check_parse_errors(p);
+
+            match &program.statements[0] {
+                Statement::ExpressionStatement(x) => match &x.expression {
+                    Expression::FunctionLiteral(function) => {
+                        let params: Vec<&str> = function
+                            .parameters
+                            .iter()
+                            .map(|p| p.value.as_str())
+                            .collect();
+                        assert_eq!(
+                            params, expected_params,
+                            "parameters wrong. want {:?}, got={:?}",
+                            expected_params, params
+                        );
+                    }
+                    _ => panic!("exp is not ast.FunctionLiteral. got={:?}", x.expression),
+                },
+                stmt => panic!(
+                    "program.Statements[0] is not ast.ExpressionStatement. got={:?}",
+                    stmt
+                ),
+            };
+        }
+    }
+
+    #[test]
+    fn call_expression_parsing() {
+        let input = "add(1, 2 * 3, 4 + 5);";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        check_parse_errors(p);
+
+        assert_eq!(program.string(), "add(1, (2 * 3), (4 + 5))");
+
+        match &program.statements[0] {
+            Statement::ExpressionStatement(x) => match &x.expression {
+                Expression::CallExpression(exp) => {
+                    test_identifier(&exp.function, "add");
+                    assert_eq!(
+                        exp.arguments.len(),
+                        3,
+                        "wrong length of arguments. got={}",
+                        exp.arguments.len()
+                    );
+                    test_literal_expression(&exp.arguments[0], &Literal::Int(1));
+                    test_infix_expression(
+                        &exp.arguments[1],
+                        &Literal::Int(2),
+                        "*",
+                        &Literal::Int(3),
+                    );
+                    test_infix_expression(
+                        &exp.arguments[2],
+                        &Literal::Int(4),
+                        "+",
+                        &Literal::Int(5),
+                    );
+                }
+                _ => panic!("exp is not ast.CallExpression. got={:?}", x.expression),
+            },
+            stmt => panic!(
+                "program.Statements[0] is not ast.ExpressionStatement. got={:?}",
+                stmt
+            ),
+        };
+    }
+
+    #[test]
+    fn array_literal_parsing() {
+        let input = "[1, 2 * 2, 3 + 3]";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        check_parse_errors(p);
+
+        match &program.statements[0] {
+            Statement::ExpressionStatement(x) => match &x.expression {
+                Expression::ArrayLiteral(array) => {
+                    assert_eq!(
+                        array.elements.len(),
+                        3,
+                        "len(array.elements) not 3. got={}",
+                        array.elements.len()
+                    );
+                    test_integer_literal(&array.elements[0], 1);
+                    test_infix_expression(
+                        &array.elements[1],
+                        &Literal::Int(2),
+                        "*",
+                        &Literal::Int(2),
+                    );
+                    test_infix_expression(
+                        &array.elements[2],
+                        &Literal::Int(3),
+                        "+",
+                        &Literal::Int(3),
+                    );
+                    assert_eq!((array.span.start, array.span.end), (0, 17));
+                }
+                _ => panic!("exp not ast.ArrayLiteral. got={:?}", x.expression),
+            },
+            stmt => panic!(
+                "program.Statements[0] is not ast.ExpressionStatement. got={:?}",
+                stmt
+            ),
+        };
+    }
+
+    #[test]
+    fn empty_array_literal_parsing() {
+        let l = Lexer::new("[]");
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        check_parse_errors(p);
+
+        assert_eq!(program.string(), "[]");
+    }
+
+    #[test]
+    fn index_expression_parsing() {
+        let input = "myArray[1 + 1]";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        check_parse_errors(p);
+
+        match &program.statements[0] {
+            Statement::ExpressionStatement(x) => match &x.expression {
+                Expression::IndexExpression(exp) => {
+                    test_identifier(&exp.left, "myArray");
+                    test_infix_expression(&exp.index, &Literal::Int(1), "+", &Literal::Int(1));
+                    assert_eq!((exp.span.start, exp.span.end), (0, 14));
+                }
+                _ => panic!("exp not ast.IndexExpression. got={:?}", x.expression),
+            },
+            stmt => panic!(
+                "program.Statements[0] is not ast.ExpressionStatement. got={:?}",
+                stmt
+            ),
+        };
+    }
+
+    #[test]
+    fn hash_literal_parsing() {
+        let tests = vec![
+            (
+                r#"{"one": 1, "two": 2, "three": 3}"#,
+                r#"{"one": 1, "two": 2, "three": 3}"#,
+            ),
+            ("{}", "{}"),
+            ("{1: true, false: 2,}", "{1: true, false: 2}"),
+            (
+                r#"{"one": 0 + 1, "two": 10 - 8, x: 15 / 5}"#,
+                r#"{"one": (0 + 1), "two": (10 - 8), x: (15 / 5)}"#,
+            ),
+            (r#"{"a": {"b": [1]}}["a"]"#, r#"({"a": {"b": [1]}}["a"])"#),
+            ("if (x) { {} }", "ifx {}"),
+        ];
+
+        for (input, expected) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            let program = p.parse_program();
+            check_parse_errors(p);
+
+            assert_eq!(program.string(), expected);
+        }
+    }
+
+    #[test]
+    fn hash_literal_pairs() {
+        let input = r#"{"one": 1, "two": 2 * 3}"#;
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        check_parse_errors(p);
+
+        match &program.statements[0] {
+            Statement::ExpressionStatement(x) => match &x.expression {
+                Expression::HashLiteral(hash) => {
+                    assert_eq!(
+                        hash.pairs.len(),
+                        2,
+                        "hash.Pairs has wrong length. got={}",
+                        hash.pairs.len()
+                    );
+                    assert_eq!(hash.pairs[0].0.string(), r#""one""#);
+                    test_integer_literal(&hash.pairs[0].1, 1);
+                    assert_eq!(hash.pairs[1].0.string(), r#""two""#);
+                    test_infix_expression(
+                        &hash.pairs[1].1,
+                        &Literal::Int(2),
+                        "*",
+                        &Literal::Int(3),
+                    );
+                    assert_eq!((hash.span.start, hash.span.end), (0, 24));
+                }
+                _ => panic!("exp is not ast.HashLiteral. got={:?}", x.expression),
+            },
+            stmt => panic!(
+                "program.Statements[0] is not ast.ExpressionStatement. got={:?}",
+                stmt
+            ),
+        };
+    }
+
+    #[test]
+    fn hash_literal_errors() {
+        let tests = vec![
+            (
+                r#"{"a" 1}"#,
+                vec!["expected next token to be `:`, got integer instead"],
+            ),
+            (
+                r#"{"a": 1 "b": 2}"#,
+                vec!["expected next token to be `,`, got string instead"],
+            ),
+            (
+                r#"{"a": 1"#,
+                vec!["expected next token to be `}`, got end of input instead"],
+            ),
+            (
+                r#"{"a": }; 1"#,
+                vec!["expected an expression, got `}` instead"],
+            ),
+            (
+                r#"{"a": {"b" 2}, "c": 3}; 4"#,
+                vec!["expected next token to be `:`, got integer instead"],
+            ),
+            (
+                r#"{"f": fn() { let; 1 }, "g" 2}"#,
+                vec![
+                    "expected next token to be identifier, got `;` instead",
+                    "expected next token to be `:`, got integer instead",
+                ],
+            ),
+            (
+                "{ let x = 1; x }",
+                vec!["expected an expression, got a block instead"],
+            ),
+            (
+                "[{ x }, { y; }]",
+                vec!["expected an expression, got a block instead"],
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            p.parse_program();
+
+            let errors: Vec<&String> = p.errors().iter().map(|e| &e.message).collect();
+            assert_eq!(errors, expected, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn node_spans() {
+        let input = "let add = fn(x, y) {\n  x + y;\n};\nadd(1, -2)";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        check_parse_errors(p);
+
+        let span_text = |span: Span| &input[span.start..span.end];
+
+        let let_stmt = match &program.statements[0] {
+            Statement::LetStatement(x) => x,
+            stmt => panic!("Expected letStatement, found {:?}", stmt),
+        };
+        assert_eq!(
+            span_text(let_stmt.span),
+            "let add = fn(x, y) {\n  x + y;\n};"
+        );
+        assert_eq!(span_text(let_stmt.name.span()), "add");
+        assert_eq!(span_text(let_stmt.value.span()), "fn(x, y) {\n  x + y;\n}");
+
+        let function = match &let_stmt.value {
+            Expression::FunctionLiteral(x) => x,
+            exp => panic!("exp is not ast.FunctionLiteral. got={:?}", exp),
+        };
+        let body = &function.body.statements[0];
+        assert_eq!(span_text(body.span()), "x + y;");
+        assert_eq!((body.span().line, body.span().column), (2, 3));
+
+        let call = &program.statements[1];
+        assert_eq!(span_text(call.span()), "add(1, -2)");
+        assert_eq!((call.span().line, call.span().column), (4, 1));
+        match call {
+            Statement::ExpressionStatement(x) => match &x.expression {
+                Expression::CallExpression(c) => {
+                    assert_eq!(span_text(c.arguments[1].span()), "-2");
+                }
+                exp => panic!("exp is not ast.CallExpression. got={:?}", exp),
+            },
+            stmt => panic!("stmt is not ast.ExpressionStatement. got={:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        let input = "let x 5;\n  let y 10;";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        p.parse_program();
+
+        let errors = p.errors();
+        assert_eq!(errors.len(), 2, "wrong number of errors. got={:?}", errors);
+        assert_eq!(
+            errors[0].to_string(),
+            "1:7: error[E0002]: expected next token to be `=`, got integer instead"
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            "2:9: error[E0002]: expected next token to be `=`, got integer instead"
+        );
+    }
+
+    #[test]
+    fn diagnostics() {
+        let input = "let y = (1 + 2 x;\nlet f = fn(x) { x;";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        p.parse_program();
+
+        let errors = p.errors();
+        assert_eq!(errors.len(), 2, "wrong number of errors. got={:?}", errors);
+
+        assert_eq!(errors[0].code, "E0002");
+        assert_eq!(
+            errors[0].message,
+            "expected next token to be `)`, got identifier instead"
+        );
+        assert_eq!(
+            errors[0].secondary[0].span,
+            Span {
+                start: 8,
+                end: 9,
+                line: 1,
+                column: 9
+            }
+        );
+
+        assert_eq!(errors[1].code, "E0002");
+        assert_eq!(errors[1].message, "expected `}`, got end of input instead");
+        assert_eq!(errors[1].secondary[0].span.column, 15);
+
+        assert_eq!(
+            errors[0].render(input, RenderMode::Plain),
+            "error[E0002]: expected next token to be `)`, got identifier instead
+ --> 1:16
+  |
+1 | let y = (1 + 2 x;
+  |         - unclosed delimiter
+  |                ^ expected `)`
+"
+        );
+    }
+
+    #[test]
+    fn number_errors() {
+        let mut tests = vec![
+            ("1 + 0b102", "invalid digit `2` in binary literal", (4, 9)),
+            ("[0x]", "missing digits after `0x`", (1, 3)),
+            ("f(1e400)", "float literal is too large", (2, 7)),
+            ("1__0", "`_` can only separate two digits", (0, 4)),
+            ("x * 0.5_", "`_` can only separate two digits", (4, 8)),
+            ("[0x_FF]", "`_` can only separate two digits", (1, 6)),
+            ("1e1_0 + 1", "`_` can't be used in an exponent", (0, 5)),
+        ];
+        // Any integer fits when they are arbitrary-precision
+        if !cfg!(feature = "bigint") {
+            tests.push((
+                "let x = 9223372036854775808;",
+                "integer literal is too large, the maximum is 9223372036854775807",
+                (8, 27),
+            ));
+        }
+
+        for (input, message, (start, end)) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            p.parse_program();
+
+            let errors = p.errors();
+            assert_eq!(errors.len(), 1, "input {}, got={:?}", input, errors);
+            assert_eq!(errors[0].code, "E0004");
+            assert_eq!(errors[0].message, message);
+            assert_eq!((errors[0].span.start, errors[0].span.end), (start, end));
+        }
+    }
+
+    #[test]
+    fn error_recovery() {
+        let input = "let x 5;
+                     let y = 10;
+                     5 + ;
+                     let f = fn(a { a * 2 };
+                     return @;
+                     let z = y * 2;
+                     if (z > 1) { let = 3; z } else { ) };
+                     z;";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+
+        let errors: Vec<(usize, &str)> = p.errors().iter().map(|e| (e.span.line, e.code)).collect();
+        assert_eq!(
+            errors,
+            vec![
+                (1, "E0002"),
+                (3, "E0003"),
+                (4, "E0002"),
+                (5, "E0001"),
+                (7, "E0002"),
+                (7, "E0003"),
+            ],
+            "wrong errors. got={:?}",
+            p.errors()
+        );
+
+        let statements: Vec<String> = program.statements.iter().map(|s| s.string()).collect();
+        assert_eq!(
+            statements,
+            vec!["let y = 10;", "let z = (y * 2);", "if(z > 1) zelse ", "z"]
+        );
+
+        // The `}` the bad statement stopped on still closes its block
+        let mut tests = vec![
+            (
+                "let f = fn() { x + }; let y = 1;",
+                vec!["let f = fn() ;", "let y = 1;"],
+            ),
+            (
+                "if (x) { 1 + } else { 2 }; let z = 3;",
+                vec!["ifx else 2", "let z = 3;"],
+            ),
+            ("fn() { if (x) { let = 1 } }; 2", vec!["fn() ifx ", "2"]),
+        ];
+        // A block where an expression is expected is skipped as a whole
+        tests.push(("{ let a = 1; a }; let b = 2;", vec!["let b = 2;"]));
+        tests.push(("let c = { x }; c", vec!["c"]));
+
+        for (input, expected) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            let program = p.parse_program();
+
+            let codes: Vec<&str> = p.errors().iter().map(|e| e.code).collect();
+            assert_eq!(codes.len(), 1, "input {}, got={:?}", input, p.errors());
+
+            let statements: Vec<String> = program.statements.iter().map(|s| s.string()).collect();
+            assert_eq!(statements, expected, "input {}", input);
+        }
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let deep = vec![
+            format!("{}1", "(".repeat(100000)),
+            format!("{}1", "-".repeat(100000)),
+            format!("{}true", "!".repeat(100000)),
+            format!("{}1", "[".repeat(100000)),
+            format!("{}1{}", "if (x) { ".repeat(1000), " }".repeat(1000)),
+        ];
+        for input in &deep {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            p.parse_program();
+
+            let codes: Vec<&str> = p.errors().iter().map(|e| e.code).collect();
+            assert_eq!(codes, vec!["E0008"], "got={:?}", p.errors());
+        }
+
+        let input = format!(
+            "let a = {}1{}; let b = 2;",
+            "(".repeat(100),
+            ")".repeat(100)
+        );
+        let l = Lexer::new(&input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+
+        assert_eq!(p.errors().len(), 1, "got={:?}", p.errors());
+        assert_eq!(
+            p.errors()[0].to_string(),
+            "1:73: error[E0008]: expression nested too deeply, the limit is 64 levels"
+        );
+        let statements: Vec<String> = program.statements.iter().map(|s| s.string()).collect();
+        assert_eq!(statements, vec!["let b = 2;"]);
+
+        let input = format!("{}1", "-".repeat(MAX_NESTING - 1));
+        let l = Lexer::new(&input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        check_parse_errors(p);
+        assert_eq!(program.statements.len(), 1);
+    }
+
+    #[test]
+    fn malformed_programs_do_not_panic() {
+        let inputs = vec![
+            "5;",
+            ";",
+            "}",
+            ")",
+            "let",
+            "let x",
+            "let x =",
+            "return",
+            "fn",
+            "fn(",
+            "fn(x,",
+            "fn(x, y) {",
+            "add(1, 2",
+            "add(1,,2)",
+            "if",
+            "if (",
+            "if (x) {",
+            "if (x) { 1 } else",
+            "(((((",
+            "!-+",
+            "99999999999999999999999",
+            "let x = 1 let y = 2",
+            "}}}}{{{{",
+            "@#$%^&",
+        ];
+
+        for input in inputs {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            p.parse_program();
+        }
+    }
+
+    #[test]
+    fn lookahead() {
+        let l = Lexer::new("let x = fn(y) { y };");
+        let mut p = Parser::new(l);
+
+        let expected = vec![
+            (0, LET),
+            (1, IDENT),
+            (2, ASSIGN),
+            (4, LPAREN),
+            (10, SEMICOLON),
+        ];
+        for (n, kind) in expected {
+            let tok = p.peek_nth(n);
+            assert_eq!(tok.kind, kind, "wrong token {} ahead. got={:?}", n, tok);
+        }
+        assert_eq!(p.peek_nth(20).kind, EOF);
+
+        let program = p.parse_program();
+        assert!(p.errors().is_empty(), "got={:?}", p.errors());
+        assert_eq!(program.string(), "let x = fn(y) y;");
+    }
+
+    enum Literal<'a> {
+        Int(i64),
+        Bool(bool),
+        Ident(&'a str),
+    }
+
+    fn test_integer_literal(exp: &Expression, value: i64) {
+        match exp {
+            Expression::IntegerLiteral(x) => {
+                assert_eq!(
+                    x.value,
+                    integer::from_i64(value),
+                    "integ.Value not {}. got={}",
+                    value,
+                    x.value
+                );
+                assert_eq!(
+                    x.token.lexeme,
+                    value.to_string(),
+                    "integ.TokenLiteral not {}. got={}",
+                    value,
+                    x.token.lexeme
+                );
+            }
+            _ => panic!("exp not *ast.IntegerLiteral. got={:?}", exp),
+        }
+    }
+
+    fn test_boolean(exp: &Expression, value: bool) {
+        match exp {
+            Expression::Boolean(x) => {
+                assert_eq!(x.value, value, "bo.Value not {}. got={}", value, x.value);
+                assert_eq!(
+                    x.token.lexeme,
+                    value.to_string(),
+                    "bo.TokenLiteral not {}. got={}",
+                    value,
+                    x.token.lexeme
+                );
+            }
+            _ => panic!("exp not *ast.Boolean. got={:?}", exp),
+        }
+    }
+
+    fn test_identifier(exp: &Expression, value: &str) {
+        match exp {
+            Expression::Identifier(x) => {
+                assert_eq!(x.value, value, "ident.Value not {}. got={}", value, x.value);
+                assert_eq!(
+                    x.token.lexeme, value,
+                    "ident.TokenLiteral not {}. got={}",
+                    value, x.token.lexeme
+                );
+            }
+            _ => panic!("exp not *ast.Identifier. got={:?}", exp),
+        }
+    }
+
+    fn test_literal_expression(exp: &Expression, expected: &Literal) {
+        match expected {
+            Literal::Int(v) => test_integer_literal(exp, *v),
+            Literal::Bool(v) => test_boolean(exp, *v),
+            Literal::Ident(v) => test_identifier(exp, v),
+        }
+    }
+
+    fn test_infix_expression(exp: &Expression, left: &Literal, operator: &str, right: &Literal) {
+        match exp {
+            Expression::InfixExpression(x) => {
+                test_literal_expression(&x.left, left);
+                assert_eq!(
+                    x.operator, operator,
+                    "exp.Operator is not '{}'. got={}",
+                    operator, x.operator
+                );
+                test_literal_expression(&x.right, right);
+            }
+            _ => panic!("exp is not ast.InfixExpression. got={:?}", exp),
+        }
+    }
 }
@@ -1,27 +1,513 @@
+use crate::ast::{Node, Program, Statement};
+use crate::compiler::Compiler;
+use crate::diagnostic::RenderMode;
+use crate::editor::{Input, LineEditor, LineReader, PlainReader};
+use crate::environment::Environment;
+use crate::eval::eval_program;
 use crate::lexer::Lexer;
+use crate::object::Object;
+use crate::parser::Parser;
+use crate::symbol_table::{SymbolScope, SymbolTable};
 use crate::token::*;
-use std::io::{self, Write};
+use crate::vm::{GLOBALS_SIZE, VM};
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::rc::Rc;
+use typed_arena::Arena;
 
 const PROMPT: &str = ">>";
+const CONTINUATION_PROMPT: &str = "..";
+
+const HELP: &str = "Commands:
+  :tokens          toggle printing the tokens of every input
+  :ast             toggle printing the parsed program of every input
+  :env             list the current bindings
+  :load <file>     run a script in the current session
+  :reset           forget all bindings
+  :mode eval|vm    run with the tree-walking evaluator or the bytecode VM
+  :help            show this message
+  :quit            leave the REPL
+Input with unclosed parens or braces, or ending in an operator, continues on the next
+line. An empty line runs it as it is. In a terminal, Tab completes keywords and bound
+names, Ctrl-R searches the history kept in ~/.monkey_history.
+";
 
 pub fn start() {
     let stdin = io::stdin();
-    let mut stdout = io::stdout();
+    let stdout = io::stdout();
+
+    let mode = if stdout.is_terminal() {
+        RenderMode::Color
+    } else {
+        RenderMode::Plain
+    };
+
+    if stdin.is_terminal() {
+        if let Ok(editor) = LineEditor::new() {
+            return run(editor, stdout.lock(), mode);
+        }
+    }
+    run(PlainReader::new(stdin.lock()), stdout.lock(), mode);
+}
+
+// Read, evaluate and print until end of input or `:quit`. Every input is evaluated in the
+// same session, so bindings from earlier inputs stay visible.
+fn run<L: LineReader, W: Write>(mut input: L, mut output: W, mode: RenderMode) {
+    // Functions bound in the session point into the input they were defined in, so the inputs
+    // are kept until `:reset` replaces the session
+    let mut sources = Arena::new();
+    let mut session = Session::new(&sources, Settings::new(mode));
+    let mut buffer = String::new();
 
     loop {
-        write!(stdout, "{}", PROMPT).unwrap();
-        stdout.flush().unwrap();
-        let mut input = String::new();
-        stdin.read_line(&mut input).unwrap();
-        let mut l = Lexer::new(&input[..]);
+        let prompt = if buffer.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+
+        let line = match input.read_line(prompt, &session.names(), &mut output) {
+            Input::Line(line) => line,
+            Input::Interrupted => {
+                buffer.clear();
+                continue;
+            }
+            Input::End => {
+                writeln!(output).unwrap();
+                return;
+            }
+        };
+
+        if buffer.is_empty() && line.trim_start().starts_with(':') {
+            match session.command(line.trim(), &mut output) {
+                Action::Continue => (),
+                Action::Reset => {
+                    let settings = session.settings;
+                    drop(session);
+                    sources = Arena::new();
+                    session = Session::new(&sources, settings);
+                    writeln!(output, "session reset").unwrap();
+                }
+                Action::Quit => return,
+            }
+            continue;
+        }
+
+        let blank = line.trim().is_empty();
+        buffer.push_str(&line);
+        buffer.push('\n');
+        if !blank && is_incomplete(&buffer) {
+            continue;
+        }
+
+        session.eval(std::mem::take(&mut buffer), &mut output);
+    }
+}
+
+// Input that can not be a whole program yet: brackets of any kind or a string are still open, or
+// the last token is an operator waiting for its right hand side
+fn is_incomplete(input: &str) -> bool {
+    let mut l = Lexer::new(input);
+    let mut depth = 0;
+    let mut last = EOF;
+
+    for tok in l.by_ref().take_while(|tok| tok.kind != EOF) {
+        match tok.kind {
+            LPAREN | LBRACE | LBRACKET => depth += 1,
+            RPAREN | RBRACE | RBRACKET => depth -= 1,
+            _ => (),
+        }
+        last = tok.kind;
+    }
+
+    if l.take_errors()
+        .iter()
+        .any(|e| e.code == "E0005" || e.code == "E0007")
+    {
+        return true;
+    }
+
+    match last {
+        ASSIGN | PLUS | MINUS | ASTERISK | SLASH | BANG | LT | GT | EQ | NOTEQ | COMMA | COLON => {
+            true
+        }
+        _ => depth > 0,
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+enum Mode {
+    Eval,
+    Vm,
+}
+
+// What the REPL does after a meta-command
+enum Action {
+    Continue,
+    Reset, // Start a new session
+    Quit,
+}
+
+// Options set with meta-commands, they survive a `:reset`
+#[derive(Clone, Copy)]
+struct Settings {
+    render_mode: RenderMode,
+    mode: Mode,
+    show_tokens: bool,
+    show_ast: bool,
+}
+
+impl Settings {
+    fn new(render_mode: RenderMode) -> Settings {
+        Settings {
+            render_mode,
+            mode: Mode::Eval,
+            show_tokens: false,
+            show_ast: false,
+        }
+    }
+}
+
+// Everything the REPL remembers between inputs. Each mode keeps its own bindings.
+struct Session<'a> {
+    settings: Settings,
+    sources: &'a Arena<String>, // Every input of the session
+
+    env: Rc<RefCell<Environment<'a>>>,
+
+    symbol_table: SymbolTable,
+    constants: Vec<Object<'a>>,
+    globals: Vec<Option<Object<'a>>>,
+}
+
+impl<'a> Session<'a> {
+    fn new(sources: &'a Arena<String>, settings: Settings) -> Session<'a> {
+        Session {
+            settings,
+            sources,
+            env: Environment::new(),
+            symbol_table: SymbolTable::new(),
+            constants: vec![],
+            globals: vec![None; GLOBALS_SIZE],
+        }
+    }
+
+    fn command<W: Write>(&mut self, line: &str, output: &mut W) -> Action {
+        // The argument is the whole rest of the line, so a path can contain spaces
+        let (command, argument) = match line.trim().split_once(char::is_whitespace) {
+            Some((command, rest)) => (command, Some(rest.trim())),
+            None => (line.trim(), None),
+        };
+
+        match (command, argument) {
+            (":quit", None) => return Action::Quit,
+            (":help", None) => write!(output, "{}", HELP).unwrap(),
+            (":tokens", None) => {
+                self.settings.show_tokens = !self.settings.show_tokens;
+                writeln!(output, "tokens {}", on_off(self.settings.show_tokens)).unwrap();
+            }
+            (":ast", None) => {
+                self.settings.show_ast = !self.settings.show_ast;
+                writeln!(output, "ast {}", on_off(self.settings.show_ast)).unwrap();
+            }
+            (":env", None) => self.print_env(output),
+            (":reset", None) => return Action::Reset,
+            (":mode", Some("eval")) => {
+                self.settings.mode = Mode::Eval;
+                writeln!(output, "mode eval").unwrap();
+            }
+            (":mode", Some("vm")) => {
+                self.settings.mode = Mode::Vm;
+                writeln!(output, "mode vm").unwrap();
+            }
+            (":load", Some(path)) => match fs::read_to_string(path) {
+                Ok(source) => self.eval(source, output),
+                Err(err) => writeln!(output, "cannot read {}: {}", path, err).unwrap(),
+            },
+            _ => writeln!(output, "unknown command `{}`, try :help", line).unwrap(),
+        }
+        Action::Continue
+    }
+
+    fn eval<W: Write>(&mut self, source: String, output: &mut W) {
+        let source: &'a str = self.sources.alloc(source);
+
+        if self.settings.show_tokens {
+            for tok in Lexer::new(source).take_while(|tok| tok.kind != EOF) {
+                writeln!(output, "{:?}", tok).unwrap();
+            }
+        }
+
+        let l = Lexer::new(source);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+
+        if !p.errors().is_empty() {
+            for e in p.errors() {
+                write!(output, "{}", e.render(source, self.settings.render_mode)).unwrap();
+            }
+            return;
+        }
 
-        loop {
-            let tok = l.next_token();
-            if tok.ttype == EOF {
-                break;
+        if self.settings.show_ast {
+            for s in &program.statements {
+                writeln!(output, "{}", s.string()).unwrap();
             }
-            write!(stdout, "{:?}\n", tok).unwrap();
-            stdout.flush().unwrap();
         }
+
+        let result = match self.settings.mode {
+            Mode::Eval => match eval_program(&program, &self.env) {
+                Object::Error(msg) => Err(msg),
+                obj => Ok(obj),
+            },
+            Mode::Vm => self.run_vm(&program),
+        };
+
+        match (result, program.statements.last()) {
+            (Err(msg), _) => writeln!(output, "ERROR: {}", msg).unwrap(),
+            (Ok(_), None) | (Ok(_), Some(Statement::LetStatement(_))) => (),
+            (Ok(obj), _) => writeln!(output, "{}", obj.inspect()).unwrap(),
+        }
+    }
+
+    fn run_vm(&mut self, program: &Program<'a>) -> Result<Object<'a>, String> {
+        let symbol_table = std::mem::take(&mut self.symbol_table);
+        let constants = std::mem::take(&mut self.constants);
+
+        let mut compiler = Compiler::new_with_state(symbol_table, constants);
+        let compiled = compiler.compile(program);
+        let bytecode = compiler.bytecode();
+
+        let (symbol_table, constants) = compiler.into_state();
+        self.symbol_table = symbol_table;
+        self.constants = constants;
+        compiled?;
+
+        let globals = std::mem::take(&mut self.globals);
+        let mut vm = VM::new_with_globals(bytecode, globals);
+        let ran = vm.run();
+        let result = vm.last_popped_stack_elem();
+        self.globals = vm.into_globals();
+
+        ran.map(|_| result)
+    }
+
+    // The names bound in the current mode
+    fn names(&self) -> Vec<String> {
+        match self.settings.mode {
+            Mode::Eval => self
+                .env
+                .borrow()
+                .bindings()
+                .into_iter()
+                .map(|(name, _)| name.clone())
+                .collect(),
+            Mode::Vm => self
+                .symbol_table
+                .symbols()
+                .into_iter()
+                .filter(|s| s.scope == SymbolScope::Global && self.globals[s.index].is_some())
+                .map(|s| s.name.clone())
+                .collect(),
+        }
+    }
+
+    fn print_env<W: Write>(&self, output: &mut W) {
+        match self.settings.mode {
+            Mode::Eval => {
+                for (name, value) in self.env.borrow().bindings() {
+                    writeln!(output, "{} = {}", name, value.inspect()).unwrap();
+                }
+            }
+            Mode::Vm => {
+                let mut symbols = self.symbol_table.symbols();
+                symbols.retain(|s| s.scope == SymbolScope::Global);
+                symbols.sort_by(|a, b| a.name.cmp(&b.name));
+
+                for s in symbols {
+                    // Names that were only used are reserved a global without a value
+                    if let Some(value) = &self.globals[s.index] {
+                        writeln!(output, "{} = {}", s.name, value.inspect()).unwrap();
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_repl(input: &str) -> String {
+        let mut output = vec![];
+        run(
+            PlainReader::new(input.as_bytes()),
+            &mut output,
+            RenderMode::Plain,
+        );
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn persistent_environment() {
+        let output =
+            run_repl("let x = 5;\nlet double = fn(n) { n * 2 };\ndouble(x)\nif (x > 10) { 1 }\n");
+
+        assert_eq!(output, ">>>>>>10\n>>null\n>>\n");
+    }
+
+    #[test]
+    fn errors() {
+        let output = run_repl("let x 5;\nfoo\n");
+
+        assert_eq!(
+            output,
+            ">>error[E0002]: expected next token to be `=`, got integer instead
+ --> 1:7
+  |
+1 | let x 5;
+  |       ^ expected `=`
+  |
+  = help: a binding looks like `let <name> = <expression>;`
+>>ERROR: identifier not found: foo
+>>
+"
+        );
+    }
+
+    #[test]
+    fn quit() {
+        let output = run_repl("1 + 1\n:quit\n2 + 2\n");
+
+        assert_eq!(output, ">>2\n>>");
+    }
+
+    #[test]
+    fn incomplete_input() {
+        assert!(is_incomplete("let add = fn(x, y) {"));
+        assert!(is_incomplete("add(1,"));
+        assert!(is_incomplete("[1, 2"));
+        assert!(is_incomplete("1 +"));
+        assert!(is_incomplete("let x ="));
+        assert!(is_incomplete("let s = \"multi\nline"));
+        assert!(is_incomplete("/* multi\nline"));
+        assert!(is_incomplete("1 + // more to come"));
+        assert!(!is_incomplete("let add = fn(x, y) { x + y };"));
+        assert!(!is_incomplete("1 + 2"));
+        assert!(!is_incomplete("\"{\""));
+        assert!(!is_incomplete(")"));
+        assert!(!is_incomplete("1 /* ( */"));
+    }
+
+    #[test]
+    fn multi_line_input() {
+        let output = run_repl("let add = fn(x, y) {\n  x +\n    y\n};\nadd(1,\n2)\n(1 +\n\n");
+
+        assert_eq!(
+            output,
+            ">>......>>..3\n>>..error[E0003]: expected an expression, got end of input instead
+ --> 3:1
+  |
+3 | 
+  | ^ expected an expression
+error[E0002]: expected next token to be `)`, got end of input instead
+ --> 3:1
+  |
+1 | (1 +
+  | - unclosed delimiter
+3 | 
+  | ^ expected `)`
+>>
+"
+        );
+    }
+
+    #[test]
+    fn show_tokens_and_ast() {
+        let output = run_repl(":tokens\nx\n:tokens\n:ast\n-1 * 2\n");
+
+        assert_eq!(
+            output,
+            ">>tokens on
+>>Token { kind: IDENT, lexeme: \"x\", span: Span { start: 0, end: 1, line: 1, column: 1 } }
+ERROR: identifier not found: x
+>>tokens off
+>>ast on
+>>((-1) * 2)
+-2
+>>
+"
+        );
+    }
+
+    #[test]
+    fn env_and_reset() {
+        let output = run_repl("let b = true;\nlet a = 1;\n:env\n:reset\n:env\na\n");
+
+        assert_eq!(
+            output,
+            ">>>>>>a = 1\nb = true\n>>session reset\n>>>>ERROR: identifier not found: a\n>>\n"
+        );
+    }
+
+    #[test]
+    fn vm_mode() {
+        let output = run_repl(
+            ":mode vm\nlet x = 2;\nlet f = fn(y) { x * y };\nf(21)\n:env\nx + true\n:mode eval\nx\n",
+        );
+
+        assert_eq!(
+            output,
+            ">>mode vm\n>>>>>>42\n>>f = fn(y) {\n(x * y)\n}\nx = 2\n>>ERROR: type mismatch: INTEGER + BOOLEAN\n>>mode eval\n>>ERROR: identifier not found: x\n>>\n"
+        );
+    }
+
+    #[test]
+    fn vm_forward_reference() {
+        let output =
+            run_repl(":mode vm\nlet a = fn() { b() };\na()\n:env\nlet b = fn() { 7 };\na()\n");
+
+        assert_eq!(
+            output,
+            ">>mode vm\n>>>>ERROR: identifier not found: b\n>>a = fn() {\nb()\n}\n>>>>7\n>>\n"
+        );
+    }
+
+    #[test]
+    fn load_file() {
+        let path = std::env::temp_dir().join("monkey_repl_load_test.monkey");
+        fs::write(&path, "let square = fn(x) {\n  x * x\n};\n").unwrap();
+
+        let output = run_repl(&format!(":load {}\nsquare(7)\n", path.display()));
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(output, ">>>>49\n>>\n");
+
+        let dir = std::env::temp_dir().join("monkey repl load test");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("my script.monkey");
+        fs::write(&path, "let cube = fn(x) { x * x * x };\n").unwrap();
+
+        let output = run_repl(&format!(":load  {} \ncube(3)\n", path.display()));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(output, ">>>>27\n>>\n");
+    }
+
+    #[test]
+    fn unknown_command() {
+        let output = run_repl(":mode fast\n");
+
+        assert_eq!(output, ">>unknown command `:mode fast`, try :help\n>>\n");
     }
 }
@@ -0,0 +1,259 @@
+use crate::ast::Node;
+use crate::diagnostic::RenderMode;
+use crate::environment::Environment;
+use crate::eval::eval_program;
+use crate::lexer::Lexer;
+use crate::object::Object;
+use crate::parser::Parser;
+use crate::token::EOF;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+
+// Exit status of the `monkey` command, following the BSD sysexits conventions
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_USAGE: i32 = 64;
+pub const EXIT_PARSE_ERROR: i32 = 65;
+pub const EXIT_NO_INPUT: i32 = 66;
+pub const EXIT_RUNTIME_ERROR: i32 = 70;
+
+const USAGE: &str = "usage: monkey [--tokens] [--ast] [<script> | -]
+
+Runs the Monkey script at <script>, or reads it from stdin when given `-`.
+Without a script the interactive REPL is started.
+
+  --tokens  print the tokens of the script instead of running it
+  --ast     print the parsed program instead of running it
+";
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    path: String,
+    tokens: bool,
+    ast: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut path = None;
+
+    for arg in args {
+        match arg.as_str() {
+            "--tokens" => options.tokens = true,
+            "--ast" => options.ast = true,
+            "-" => path = Some(arg.clone()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ if path.is_some() => return Err(String::from("only one script can be run")),
+            _ => path = Some(arg.clone()),
+        }
+    }
+
+    match path {
+        Some(path) => {
+            options.path = path;
+            Ok(options)
+        }
+        None => Err(String::from("no script given")),
+    }
+}
+
+// Run the command line and return the exit status
+pub fn run(args: &[String]) -> i32 {
+    let mut stdout = io::stdout();
+    let mut stderr = io::stderr();
+
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        write!(stdout, "{}", USAGE).unwrap();
+        return EXIT_SUCCESS;
+    }
+
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            write!(stderr, "monkey: {}\n\n{}", err, USAGE).unwrap();
+            return EXIT_USAGE;
+        }
+    };
+
+    let source = if options.path == "-" {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source).map(|_| source)
+    } else {
+        fs::read_to_string(&options.path)
+    };
+    let source = match source {
+        Ok(source) => source,
+        Err(err) => {
+            writeln!(stderr, "monkey: cannot read {}: {}", options.path, err).unwrap();
+            return EXIT_NO_INPUT;
+        }
+    };
+
+    let mode = if stderr.is_terminal() && std::env::var_os("NO_COLOR").is_none() {
+        RenderMode::Color
+    } else {
+        RenderMode::Plain
+    };
+    execute(&source, &options, &mut stdout, &mut stderr, mode)
+}
+
+fn execute(
+    source: &str,
+    options: &Options,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+    mode: RenderMode,
+) -> i32 {
+    if options.tokens {
+        for tok in Lexer::new(source).take_while(|tok| tok.kind != EOF) {
+            writeln!(out, "{:?}", tok).unwrap();
+        }
+    }
+
+    let l = Lexer::new(source);
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+
+    if !p.errors().is_empty() {
+        for e in p.errors() {
+            write!(err, "{}", e.render(source, mode)).unwrap();
+        }
+        return EXIT_PARSE_ERROR;
+    }
+
+    if options.ast {
+        for s in &program.statements {
+            writeln!(out, "{}", s.string()).unwrap();
+        }
+    }
+    if options.tokens || options.ast {
+        return EXIT_SUCCESS;
+    }
+
+    let env = Environment::new();
+    match eval_program(&program, &env) {
+        Object::Error(msg) => {
+            writeln!(err, "runtime error: {}", msg).unwrap();
+            EXIT_RUNTIME_ERROR
+        }
+        Object::Null => EXIT_SUCCESS,
+        result => {
+            writeln!(out, "{}", result.inspect()).unwrap();
+            EXIT_SUCCESS
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn run_script(source: &str, options: Options) -> (i32, String, String) {
+        let mut out = vec![];
+        let mut err = vec![];
+        let status = execute(source, &options, &mut out, &mut err, RenderMode::Plain);
+        (
+            status,
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+        )
+    }
+
+    fn script() -> Options {
+        Options {
+            path: String::from("script.monkey"),
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn argument_parsing() {
+        assert_eq!(
+            parse_args(&args(&["--ast", "a.monkey"])),
+            Ok(Options {
+                path: String::from("a.monkey"),
+                tokens: false,
+                ast: true,
+            })
+        );
+        assert_eq!(parse_args(&args(&["-"])).unwrap().path, "-");
+        assert!(parse_args(&args(&["--tokens"])).is_err());
+        assert!(parse_args(&args(&["--bogus", "a.monkey"])).is_err());
+        assert!(parse_args(&args(&["a.monkey", "b.monkey"])).is_err());
+    }
+
+    #[test]
+    fn successful_run() {
+        let (status, out, err) = run_script("let add = fn(x, y) { x + y }; add(5, 10)", script());
+
+        assert_eq!(status, EXIT_SUCCESS);
+        assert_eq!(out, "15\n");
+        assert_eq!(err, "");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let (status, out, err) = run_script("let x 5;", script());
+
+        assert_eq!(status, EXIT_PARSE_ERROR);
+        assert_eq!(out, "");
+        assert!(
+            err.starts_with("error[E0002]: expected next token to be `=`"),
+            "got={}",
+            err
+        );
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let (status, out, err) = run_script("let x = 5; x + true", script());
+
+        assert_eq!(status, EXIT_RUNTIME_ERROR);
+        assert_eq!(out, "");
+        assert_eq!(err, "runtime error: type mismatch: INTEGER + BOOLEAN\n");
+    }
+
+    #[test]
+    fn runaway_recursion() {
+        // A debug build needs more stack than the 2 MiB of a test thread to reach the limit,
+        // the main thread of the command has 8 MiB
+        let (status, out, err) = std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(|| run_script("let f = fn(n) { f(n + 1) }; f(0)", script()))
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(status, EXIT_RUNTIME_ERROR);
+        assert_eq!(out, "");
+        assert_eq!(err, "runtime error: stack overflow\n");
+    }
+
+    #[test]
+    fn dump_ast() {
+        let options = Options {
+            ast: true,
+            ..script()
+        };
+        let (status, out, _) = run_script("let x = 1 + 2 * 3; -x", options);
+
+        assert_eq!(status, EXIT_SUCCESS);
+        assert_eq!(out, "let x = (1 + (2 * 3));\n(-x)\n");
+    }
+
+    #[test]
+    fn dump_tokens() {
+        let options = Options {
+            tokens: true,
+            ..script()
+        };
+        let (status, out, _) = run_script("x + 1", options);
+
+        assert_eq!(status, EXIT_SUCCESS);
+        assert_eq!(out.lines().count(), 3);
+        assert!(out.starts_with("Token { kind: IDENT, lexeme: \"x\""));
+    }
+}
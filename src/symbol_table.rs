@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum SymbolScope {
+    Global,
+    Local,
+    Free,     // Local of an enclosing function captured by a closure
+    Function, // The name of the function currently being compiled
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    pub outer: Option<Box<SymbolTable>>,
+    pub free_symbols: Vec<Symbol>,
+    store: HashMap<String, Symbol>,
+    pub num_definitions: usize,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    pub fn new_enclosed(outer: SymbolTable) -> SymbolTable {
+        SymbolTable {
+            outer: Some(Box::new(outer)),
+            ..SymbolTable::default()
+        }
+    }
+
+    // A global keeps its index when it is bound again, so functions compiled before the last
+    // `let` of a name read its latest value like the evaluator does
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let scope = if self.outer.is_none() {
+            SymbolScope::Global
+        } else {
+            SymbolScope::Local
+        };
+        if let Some(symbol) = self.store.get(name) {
+            if symbol.scope == SymbolScope::Global {
+                return symbol.clone();
+            }
+        }
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope,
+            index: self.num_definitions,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+        self.num_definitions += 1;
+        symbol
+    }
+
+    // Global for a name that no `let` has bound yet, e.g. a function defined further down.
+    // Reading it is an error until a binding is made at run time.
+    pub fn declare_global(&mut self, name: &str) -> Symbol {
+        match &mut self.outer {
+            Some(outer) => outer.declare_global(name),
+            None => self.define(name),
+        }
+    }
+
+    // Every symbol defined in this scope, in no particular order
+    pub fn symbols(&self) -> Vec<&Symbol> {
+        self.store.values().collect()
+    }
+
+    pub fn define_function_name(&mut self, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Function,
+            index: 0,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    // Locals of enclosing functions are turned into free symbols of this scope, so the closure
+    // can carry their values with it
+    pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.store.get(name) {
+            return Some(symbol.clone());
+        }
+
+        let symbol = self.outer.as_mut()?.resolve(name)?;
+        match symbol.scope {
+            SymbolScope::Global => Some(symbol),
+            _ => Some(self.define_free(symbol)),
+        }
+    }
+
+    fn define_free(&mut self, original: Symbol) -> Symbol {
+        let symbol = Symbol {
+            name: original.name.clone(),
+            scope: SymbolScope::Free,
+            index: self.free_symbols.len(),
+        };
+        self.free_symbols.push(original);
+        self.store.insert(symbol.name.clone(), symbol.clone());
+        symbol
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            scope,
+            index,
+        }
+    }
+
+    #[test]
+    fn define_and_resolve() {
+        let mut global = SymbolTable::new();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.define("c"), symbol("c", SymbolScope::Local, 0));
+
+        assert_eq!(
+            local.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+        assert_eq!(local.resolve("c"), Some(symbol("c", SymbolScope::Local, 0)));
+        assert_eq!(local.resolve("d"), None);
+    }
+
+    #[test]
+    fn define_global_again() {
+        let mut global = SymbolTable::new();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.define("c"), symbol("c", SymbolScope::Local, 0));
+        assert_eq!(local.define("c"), symbol("c", SymbolScope::Local, 1));
+        assert_eq!(
+            local.declare_global("d"),
+            symbol("d", SymbolScope::Global, 2)
+        );
+        assert_eq!(
+            local.resolve("d"),
+            Some(symbol("d", SymbolScope::Global, 2))
+        );
+    }
+
+    #[test]
+    fn resolve_free() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        let mut first_local = SymbolTable::new_enclosed(global);
+        first_local.define("c");
+
+        let mut second_local = SymbolTable::new_enclosed(first_local);
+        second_local.define("e");
+
+        assert_eq!(
+            second_local.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+        assert_eq!(
+            second_local.resolve("c"),
+            Some(symbol("c", SymbolScope::Free, 0))
+        );
+        assert_eq!(
+            second_local.resolve("e"),
+            Some(symbol("e", SymbolScope::Local, 0))
+        );
+        assert_eq!(
+            second_local.free_symbols,
+            vec![symbol("c", SymbolScope::Local, 0)]
+        );
+    }
+
+    #[test]
+    fn shadowing_function_name() {
+        let mut global = SymbolTable::new();
+        global.define_function_name("a");
+        global.define("a");
+
+        assert_eq!(
+            global.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+    }
+}
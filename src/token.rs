@@ -1,81 +1,134 @@
-pub const ILLEGAL: TokenType = TokenType::ILLEGAL("ILLEGAL");
-pub const EOF: TokenType = TokenType::EOF("EOF");
-pub const IDENT: TokenType = TokenType::IDENT("IDENT");
-pub const INT: TokenType = TokenType::INT("INT");
-pub const ASSIGN: TokenType = TokenType::ASSIGN("=");
-pub const EQ: TokenType = TokenType::EQ("==");
-pub const NOTEQ: TokenType = TokenType::NOTEQ("!=");
-pub const PLUS: TokenType = TokenType::PLUS("+");
-pub const MINUS: TokenType = TokenType::MINUS("-");
-pub const BANG: TokenType = TokenType::BANG("!");
-pub const ASTERISK: TokenType = TokenType::ASTERISK("*");
-pub const SLASH: TokenType = TokenType::SLASH("/");
-pub const LT: TokenType = TokenType::LT("<");
-pub const GT: TokenType = TokenType::GT(">");
-pub const COMMA: TokenType = TokenType::COMMA(",");
-pub const SEMICOLON: TokenType = TokenType::SEMICOLON(";");
-pub const LPAREN: TokenType = TokenType::LPAREN("(");
-pub const RPAREN: TokenType = TokenType::RPARENT(")");
-pub const LBRACE: TokenType = TokenType::LBRACE("{");
-pub const RBRACE: TokenType = TokenType::RBRACE("}");
-pub const FUNCTION: TokenType = TokenType::FUNCTION("FUNCTION");
-pub const LET: TokenType = TokenType::LET("LET");
-pub const TRUE: TokenType = TokenType::TRUE("TRUE");
-pub const FALSE: TokenType = TokenType::FALSE("FALSE");
-pub const IF: TokenType = TokenType::IF("IF");
-pub const ELSE: TokenType = TokenType::ELSE("ELSE");
-pub const RETURN: TokenType = TokenType::RETURN("RETURN");
+use std::fmt;
 
-#[derive(PartialEq, Eq, Debug, Clone)]
-pub enum TokenType<'a> {
-    ILLEGAL(&'a str),
-    EOF(&'a str),
+pub use self::TokenKind::*;
+
+// What a token is, its text is in Token::lexeme. The variants are in scope wherever the
+// module is glob imported, so they are used unqualified: `tok.kind == EOF`.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum TokenKind {
+    ILLEGAL,
+    EOF,
     // Identifiers + literals
-    IDENT(&'a str), // add, foobar, x, y, ..
-    INT(&'a str),   // 1343456
+    IDENT,   // add, foobar, x, y, ..
+    INT,     // 1343456
+    FLOAT,   // 1.5, 2e10, 1.5e-3
+    STRING,  // "foo bar"
+    COMMENT, // Only produced by Lexer::with_comments
     // Operators
-    ASSIGN(&'a str),
-    EQ(&'a str),
-    NOTEQ(&'a str),
-    PLUS(&'a str),
-    MINUS(&'a str),
-    BANG(&'a str),
-    ASTERISK(&'a str),
-    SLASH(&'a str),
-    LT(&'a str),
-    GT(&'a str),
+    ASSIGN,
+    EQ,
+    NOTEQ,
+    PLUS,
+    MINUS,
+    BANG,
+    ASTERISK,
+    SLASH,
+    LT,
+    GT,
     // Delimiters
-    COMMA(&'a str),
-    SEMICOLON(&'a str),
-    LPAREN(&'a str),
-    RPARENT(&'a str),
-    LBRACE(&'a str),
+    COMMA,
+    SEMICOLON,
+    COLON,
+    LPAREN,
+    RPAREN,
+    LBRACE,
+    RBRACE,
+    LBRACKET,
+    RBRACKET,
     // Keywords
-    RBRACE(&'a str),
-    FUNCTION(&'a str),
-    LET(&'a str),
-    TRUE(&'a str),
-    FALSE(&'a str),
-    IF(&'a str),
-    ELSE(&'a str),
-    RETURN(&'a str),
+    FUNCTION,
+    LET,
+    TRUE,
+    FALSE,
+    IF,
+    ELSE,
+    RETURN,
 }
 
-#[derive(Eq, PartialEq, Debug, Clone)]
-pub struct Token<'a> {
-    pub ttype: TokenType<'a>,
-    pub literal: String,
+// Location of a piece of source code. `start` and `end` are byte offsets into the input,
+// `line` and `column` (both starting at 1) are where `start` is.
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
 }
 
-impl<'a> Token<'a> {
-    pub fn new() -> Self {
-        Token {
-            ttype: TokenType::IDENT(""),
-            literal: String::from(""),
+impl Span {
+    // Span covering both self and everything up to the end of `other`
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            end: other.end,
+            ..*self
         }
     }
-    pub fn lookup_ident(ident: &String) -> TokenType<'a> {
-        match ident.as_str() {
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// User-facing name of the token kind, as used in diagnostics
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ILLEGAL => "illegal character",
+            EOF => "end of input",
+            IDENT => "identifier",
+            INT => "integer",
+            FLOAT => "float",
+            STRING => "string",
+            COMMENT => "comment",
+            ASSIGN => "`=`",
+            EQ => "`==`",
+            NOTEQ => "`!=`",
+            PLUS => "`+`",
+            MINUS => "`-`",
+            BANG => "`!`",
+            ASTERISK => "`*`",
+            SLASH => "`/`",
+            LT => "`<`",
+            GT => "`>`",
+            COMMA => "`,`",
+            SEMICOLON => "`;`",
+            COLON => "`:`",
+            LPAREN => "`(`",
+            RPAREN => "`)`",
+            LBRACE => "`{`",
+            RBRACE => "`}`",
+            LBRACKET => "`[`",
+            RBRACKET => "`]`",
+            FUNCTION => "`fn`",
+            LET => "`let`",
+            TRUE => "`true`",
+            FALSE => "`false`",
+            IF => "`if`",
+            ELSE => "`else`",
+            RETURN => "`return`",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Every word `Token::lookup_ident` treats as a keyword
+pub const KEYWORDS: [&str; 7] = ["fn", "let", "true", "false", "if", "else", "return"];
+
+// `lexeme` is the text of the token in the source, i.e. `&input[span.start..span.end]`. For
+// strings that is the literal with quotes and escape sequences, see lexer::string_value.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub lexeme: &'a str,
+    pub span: Span,
+}
+
+impl Token<'_> {
+    pub fn lookup_ident(ident: &str) -> TokenKind {
+        match ident {
             "fn" => FUNCTION,
             "let" => LET,
             "true" => TRUE,
@@ -87,3 +140,24 @@ impl<'a> Token<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords() {
+        for k in KEYWORDS.iter() {
+            assert_ne!(Token::lookup_ident(k), IDENT, "{} is not a keyword", k);
+        }
+        assert_eq!(Token::lookup_ident("foo"), IDENT);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(IDENT.to_string(), "identifier");
+        assert_eq!(RPAREN.to_string(), "`)`");
+        assert_eq!(FUNCTION.to_string(), "`fn`");
+        assert_eq!(EOF.to_string(), "end of input");
+    }
+}
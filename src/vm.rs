@@ -0,0 +1,715 @@
+use crate::builtins::{self, BUILTINS};
+use crate::code::{self, Opcode};
+use crate::compiler::Bytecode;
+use crate::eval;
+use crate::integer;
+use crate::object::*;
+use std::rc::Rc;
+
+const STACK_SIZE: usize = 2048;
+pub const GLOBALS_SIZE: usize = 65536;
+const MAX_FRAMES: usize = 1024;
+
+// Call frame of the function being executed
+struct Frame<'a> {
+    cl: Rc<Closure<'a>>,
+    ip: usize,           // Offset of the next instruction to execute
+    base_pointer: usize, // Stack pointer before the call, locals are stored from here on
+}
+
+pub struct VM<'a> {
+    constants: Vec<Object<'a>>,
+
+    stack: Vec<Object<'a>>,
+    sp: usize, // Always points to the next free slot. Top of the stack is stack[sp-1]
+
+    globals: Vec<Option<Object<'a>>>, // None until the `let` of the global has run
+    global_names: Vec<String>,
+
+    frames: Vec<Frame<'a>>,
+}
+
+impl<'a> VM<'a> {
+    pub fn new(bytecode: Bytecode<'a>) -> VM<'a> {
+        VM::new_with_globals(bytecode, vec![None; GLOBALS_SIZE])
+    }
+
+    // Run with the globals of an earlier VM, used by the REPL to keep its bindings
+    pub fn new_with_globals(bytecode: Bytecode<'a>, globals: Vec<Option<Object<'a>>>) -> VM<'a> {
+        let main_fn = CompiledFunction {
+            instructions: bytecode.instructions,
+            num_locals: 0,
+            num_parameters: 0,
+            source: String::new(),
+        };
+        let main_closure = Closure {
+            func: Rc::new(main_fn),
+            free: vec![],
+        };
+        let main_frame = Frame {
+            cl: Rc::new(main_closure),
+            ip: 0,
+            base_pointer: 0,
+        };
+
+        VM {
+            constants: bytecode.constants,
+            stack: vec![Object::Null; STACK_SIZE],
+            sp: 0,
+            globals,
+            global_names: bytecode.global_names,
+            frames: vec![main_frame],
+        }
+    }
+
+    pub fn into_globals(self) -> Vec<Option<Object<'a>>> {
+        self.globals
+    }
+
+    // The value of the last expression statement, the result of the program. After a stack
+    // overflow there is no slot above the top of the stack.
+    pub fn last_popped_stack_elem(&self) -> Object<'a> {
+        self.stack.get(self.sp).cloned().unwrap_or(Object::Null)
+    }
+
+    pub fn run(&mut self) -> Result<(), String> {
+        while self.current_frame().ip < self.current_frame().cl.func.instructions.len() {
+            let op = self.read_opcode()?;
+
+            match op {
+                Opcode::OpConstant => {
+                    let const_index = self.read_u16_operand();
+                    self.push(self.constants[const_index].clone())?;
+                }
+                Opcode::OpPop => {
+                    self.pop();
+                }
+                Opcode::OpAdd
+                | Opcode::OpSub
+                | Opcode::OpMul
+                | Opcode::OpDiv
+                | Opcode::OpEqual
+                | Opcode::OpNotEqual
+                | Opcode::OpGreaterThan
+                | Opcode::OpLessThan => self.execute_binary_operation(op)?,
+                Opcode::OpTrue => self.push(Object::Boolean(true))?,
+                Opcode::OpFalse => self.push(Object::Boolean(false))?,
+                Opcode::OpNull => self.push(Object::Null)?,
+                Opcode::OpBang => {
+                    let operand = self.pop();
+                    self.push(Object::Boolean(!eval::is_truthy(&operand)))?;
+                }
+                Opcode::OpMinus => {
+                    let operand = self.pop();
+                    let result = match operand {
+                        Object::Integer(value) => Object::Integer(integer::neg(&value)?),
+                        _ => eval::eval_prefix_expression("-", operand),
+                    };
+                    self.push_result(result)?;
+                }
+                Opcode::OpJump => {
+                    let pos = self.read_u16_operand();
+                    self.current_frame_mut().ip = pos;
+                }
+                Opcode::OpJumpNotTruthy => {
+                    let pos = self.read_u16_operand();
+                    let condition = self.pop();
+                    if !eval::is_truthy(&condition) {
+                        self.current_frame_mut().ip = pos;
+                    }
+                }
+                Opcode::OpSetGlobal => {
+                    let global_index = self.read_u16_operand();
+                    self.globals[global_index] = Some(self.pop());
+                }
+                Opcode::OpGetGlobal => {
+                    let global_index = self.read_u16_operand();
+                    let value = match self.globals[global_index].clone() {
+                        Some(value) => value,
+                        None => {
+                            let name = &self.global_names[global_index];
+                            match builtins::lookup(name) {
+                                Some(index) => Object::Builtin(BUILTINS[index]),
+                                None => return Err(format!("identifier not found: {}", name)),
+                            }
+                        }
+                    };
+                    self.push(value)?;
+                }
+                Opcode::OpSetLocal => {
+                    let local_index = self.read_u8_operand();
+                    let base_pointer = self.current_frame().base_pointer;
+                    self.stack[base_pointer + local_index] = self.pop();
+                }
+                Opcode::OpGetLocal => {
+                    let local_index = self.read_u8_operand();
+                    let base_pointer = self.current_frame().base_pointer;
+                    self.push(self.stack[base_pointer + local_index].clone())?;
+                }
+                Opcode::OpGetFree => {
+                    let free_index = self.read_u8_operand();
+                    let value = self.current_frame().cl.free[free_index].clone();
+                    self.push(value)?;
+                }
+                Opcode::OpCurrentClosure => {
+                    let current_closure = Rc::clone(&self.current_frame().cl);
+                    self.push(Object::Closure(current_closure))?;
+                }
+                Opcode::OpClosure => {
+                    let const_index = self.read_u16_operand();
+                    let num_free = self.read_u8_operand();
+                    self.push_closure(const_index, num_free)?;
+                }
+                Opcode::OpArray => {
+                    let num_elements = self.read_u16_operand();
+                    let elements = self.stack[self.sp - num_elements..self.sp].to_vec();
+                    self.sp -= num_elements;
+                    self.push(Object::Array(Rc::new(elements)))?;
+                }
+                Opcode::OpHash => {
+                    let num_elements = self.read_u16_operand();
+                    let hash = eval::build_hash(&self.stack[self.sp - num_elements..self.sp]);
+                    self.sp -= num_elements;
+                    self.push_result(hash)?;
+                }
+                Opcode::OpIndex => {
+                    let index = self.pop();
+                    let left = self.pop();
+                    self.push_result(eval::eval_index_expression(left, index))?;
+                }
+                Opcode::OpCall => {
+                    let num_args = self.read_u8_operand();
+                    self.call_function(num_args)?;
+                }
+                Opcode::OpReturnValue => {
+                    let return_value = self.pop();
+
+                    // A `return` at the top level ends the program with its value
+                    if self.frames.len() == 1 {
+                        self.push(return_value)?;
+                        self.pop();
+                        return Ok(());
+                    }
+
+                    let frame = self.frames.pop().unwrap();
+                    self.sp = frame.base_pointer - 1;
+                    self.push(return_value)?;
+                }
+                Opcode::OpReturn => {
+                    if self.frames.len() == 1 {
+                        return Ok(());
+                    }
+
+                    let frame = self.frames.pop().unwrap();
+                    self.sp = frame.base_pointer - 1;
+                    self.push(Object::Null)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_binary_operation(&mut self, op: Opcode) -> Result<(), String> {
+        let right = self.pop();
+        let left = self.pop();
+
+        // Integers are by far the most common operands so they skip the generic path
+        if let (Object::Integer(l), Object::Integer(r)) = (&left, &right) {
+            let result = match op {
+                Opcode::OpAdd => Object::Integer(integer::add(l, r)?),
+                Opcode::OpSub => Object::Integer(integer::sub(l, r)?),
+                Opcode::OpMul => Object::Integer(integer::mul(l, r)?),
+                Opcode::OpDiv => Object::Integer(integer::div(l, r)?),
+                Opcode::OpEqual => Object::Boolean(l == r),
+                Opcode::OpNotEqual => Object::Boolean(l != r),
+                Opcode::OpGreaterThan => Object::Boolean(l > r),
+                Opcode::OpLessThan => Object::Boolean(l < r),
+                _ => return Err(format!("unknown integer operator: {:?}", op)),
+            };
+            return self.push(result);
+        }
+
+        // Everything else shares the semantics (and error messages) of the evaluator
+        let operator = match op {
+            Opcode::OpAdd => "+",
+            Opcode::OpSub => "-",
+            Opcode::OpMul => "*",
+            Opcode::OpDiv => "/",
+            Opcode::OpEqual => "==",
+            Opcode::OpNotEqual => "!=",
+            Opcode::OpGreaterThan => ">",
+            Opcode::OpLessThan => "<",
+            _ => return Err(format!("unknown operator: {:?}", op)),
+        };
+        self.push_result(eval::eval_infix_expression(operator, left, right))
+    }
+
+    // The callee sits below its arguments on the stack, which become the first locals
+    fn call_function(&mut self, num_args: usize) -> Result<(), String> {
+        let callee = self.stack[self.sp - 1 - num_args].clone();
+        let cl = match callee {
+            Object::Closure(cl) => cl,
+            Object::Builtin(builtin) => return self.call_native(&builtin.func, num_args),
+            Object::HostFunction(host) => return self.call_native(&*host.func, num_args),
+            _ => return Err(format!("not a function: {}", callee.object_type())),
+        };
+
+        if num_args != cl.func.num_parameters {
+            return Err(format!(
+                "wrong number of arguments: want={}, got={}",
+                cl.func.num_parameters, num_args
+            ));
+        }
+
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(String::from("stack overflow"));
+        }
+
+        let base_pointer = self.sp - num_args;
+        let sp = base_pointer + cl.func.num_locals;
+        if sp >= STACK_SIZE {
+            return Err(String::from("stack overflow"));
+        }
+
+        self.frames.push(Frame {
+            cl,
+            ip: 0,
+            base_pointer,
+        });
+        self.sp = sp;
+        Ok(())
+    }
+
+    // Functions implemented in Rust run right away, their result replaces the callee and the
+    // arguments
+    fn call_native(
+        &mut self,
+        func: &dyn Fn(&[Object<'a>]) -> Object<'a>,
+        num_args: usize,
+    ) -> Result<(), String> {
+        let result = func(&self.stack[self.sp - num_args..self.sp]);
+        self.sp -= num_args + 1;
+        self.push_result(result)
+    }
+
+    fn push_closure(&mut self, const_index: usize, num_free: usize) -> Result<(), String> {
+        let func = match &self.constants[const_index] {
+            Object::CompiledFunction(func) => Rc::clone(func),
+            constant => return Err(format!("not a function: {:?}", constant)),
+        };
+
+        let free = self.stack[self.sp - num_free..self.sp].to_vec();
+        self.sp -= num_free;
+
+        self.push(Object::Closure(Rc::new(Closure { func, free })))
+    }
+
+    fn push(&mut self, obj: Object<'a>) -> Result<(), String> {
+        if self.sp >= STACK_SIZE {
+            return Err(String::from("stack overflow"));
+        }
+
+        self.stack[self.sp] = obj;
+        self.sp += 1;
+        Ok(())
+    }
+
+    // Runtime errors of the shared operator implementations abort the execution
+    fn push_result(&mut self, obj: Object<'a>) -> Result<(), String> {
+        match obj {
+            Object::Error(msg) => Err(msg),
+            _ => self.push(obj),
+        }
+    }
+
+    // The popped slot is not cleared so that last_popped_stack_elem can still see it
+    fn pop(&mut self) -> Object<'a> {
+        self.sp -= 1;
+        self.stack[self.sp].clone()
+    }
+
+    fn current_frame(&self) -> &Frame<'a> {
+        self.frames.last().unwrap()
+    }
+
+    fn current_frame_mut(&mut self) -> &mut Frame<'a> {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn read_opcode(&mut self) -> Result<Opcode, String> {
+        let frame = self.current_frame_mut();
+        let byte = frame.cl.func.instructions[frame.ip];
+        frame.ip += 1;
+        Opcode::from_byte(byte).ok_or_else(|| format!("opcode {} undefined", byte))
+    }
+
+    fn read_u16_operand(&mut self) -> usize {
+        let frame = self.current_frame_mut();
+        let operand = code::read_u16(&frame.cl.func.instructions[frame.ip..]) as usize;
+        frame.ip += 2;
+        operand
+    }
+
+    fn read_u8_operand(&mut self) -> usize {
+        let frame = self.current_frame_mut();
+        let operand = frame.cl.func.instructions[frame.ip] as usize;
+        frame.ip += 1;
+        operand
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::environment::Environment;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    // Runs the program and returns the inspected result, or the error message
+    fn run_vm(input: &str) -> Result<String, String> {
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+
+        let mut compiler = Compiler::new();
+        compiler.compile(&program)?;
+
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run()?;
+        Ok(vm.last_popped_stack_elem().inspect())
+    }
+
+    fn run_eval(input: &str) -> Result<String, String> {
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        let env = Environment::new();
+
+        match eval::eval_program(&program, &env) {
+            Object::Error(msg) => Err(msg),
+            obj => Ok(obj.inspect()),
+        }
+    }
+
+    fn run_vm_tests(tests: Vec<(&str, &str)>) {
+        for (input, expected) in tests {
+            match run_vm(input) {
+                Ok(result) => assert_eq!(
+                    result, expected,
+                    "wrong result for {}. want={}, got={}",
+                    input, expected, result
+                ),
+                Err(err) => panic!("vm error for {}: {}", input, err),
+            }
+        }
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        run_vm_tests(vec![
+            ("1", "1"),
+            ("1 + 2", "3"),
+            ("4 / 2", "2"),
+            ("50 / 2 * 2 + 10 - 5", "55"),
+            ("5 * (2 + 10)", "60"),
+            ("-10", "-10"),
+            ("-50 + 100 + -50", "0"),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", "50"),
+        ]);
+    }
+
+    #[test]
+    fn float_arithmetic() {
+        run_vm_tests(vec![
+            ("1.5", "1.5"),
+            ("-1.5", "-1.5"),
+            ("1.5 + 2", "3.5"),
+            ("7 / 2", "3"),
+            ("7 / 2.0", "3.5"),
+            ("2.5e1 * 2", "50.0"),
+            ("1 < 1.5", "true"),
+            ("2 == 2.0", "true"),
+        ]);
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn integer_overflow() {
+        for input in &["9223372036854775807 + 1", "-(-9223372036854775807 - 1)"] {
+            assert_eq!(run_vm(input), Err(String::from("integer overflow")));
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn big_integers() {
+        run_vm_tests(vec![
+            ("9223372036854775807 + 1", "9223372036854775808"),
+            ("-(-9223372036854775807 - 1)", "9223372036854775808"),
+            (
+                "let f = fn(n) { if (n < 2) { 1 } else { n * f(n - 1) } }; f(25)",
+                "15511210043330985984000000",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn boolean_expressions() {
+        run_vm_tests(vec![
+            ("true", "true"),
+            ("1 < 2", "true"),
+            ("1 > 2", "false"),
+            ("1 == 1", "true"),
+            ("true != false", "true"),
+            ("(1 < 2) == true", "true"),
+            ("!5", "false"),
+            ("!!true", "true"),
+            ("!(if (false) { 5; })", "true"),
+        ]);
+    }
+
+    #[test]
+    fn conditionals() {
+        run_vm_tests(vec![
+            ("if (true) { 10 }", "10"),
+            ("if (true) { 10 } else { 20 }", "10"),
+            ("if (false) { 10 } else { 20 } ", "20"),
+            ("if (1 > 2) { 10 }", "null"),
+            ("if (true) { }", "null"),
+            ("if ((if (false) { 10 })) { 10 } else { 20 }", "20"),
+        ]);
+    }
+
+    #[test]
+    fn string_expressions() {
+        run_vm_tests(vec![
+            (r#""monkey""#, "monkey"),
+            (r#""mon" + "key""#, "monkey"),
+            (r#""mon" + "key" + "banana""#, "monkeybanana"),
+            (r#""a\nb" == "a\nb""#, "true"),
+            (r#""a" != "a""#, "false"),
+        ]);
+    }
+
+    #[test]
+    fn array_literals() {
+        run_vm_tests(vec![
+            ("[]", "[]"),
+            ("[1, 2, 3]", "[1, 2, 3]"),
+            ("[1 + 2, 3 * 4, 5 + 6]", "[3, 12, 11]"),
+            (r#"["a", [true]]"#, "[a, [true]]"),
+        ]);
+    }
+
+    #[test]
+    fn hash_literals() {
+        run_vm_tests(vec![
+            ("{}", "{}"),
+            ("{1: 2, 2: 3}", "{1: 2, 2: 3}"),
+            ("{1 + 1: 2 * 2, 3 + 3: 4 * 4}", "{2: 4, 6: 16}"),
+            (r#"{"b": 1, "a": true, 0: [1]}"#, "{0: [1], a: true, b: 1}"),
+        ]);
+    }
+
+    #[test]
+    fn index_expressions() {
+        run_vm_tests(vec![
+            ("[1, 2, 3][1]", "2"),
+            ("[1, 2, 3][0 + 2]", "3"),
+            ("[[1, 1, 1]][0][0]", "1"),
+            ("[][0]", "null"),
+            ("[1, 2, 3][99]", "null"),
+            ("[1][-1]", "null"),
+            ("let f = fn(a) { a[1] }; f([4, 5])", "5"),
+            ("{1: 1, 2: 2}[1]", "1"),
+            ("{1: 1, 2: 2}[2]", "2"),
+            ("{1: 1}[0]", "null"),
+            ("{}[0]", "null"),
+            (r#"let k = "a"; {"a": {"b": 7}}[k]["b"]"#, "7"),
+        ]);
+    }
+
+    #[test]
+    fn builtin_functions() {
+        run_vm_tests(vec![
+            (r#"len("")"#, "0"),
+            (r#"len("four")"#, "4"),
+            ("len([1, 2, 3])", "3"),
+            ("len([])", "0"),
+            ("first([1, 2, 3])", "1"),
+            ("first([])", "null"),
+            ("last([1, 2, 3])", "3"),
+            ("rest([1, 2, 3])", "[2, 3]"),
+            ("rest([])", "null"),
+            ("push([], 1)", "[1]"),
+            (r#"puts("hello", "world!")"#, "null"),
+            ("let f = fn(a) { len(a) + 1 }; f([1, 2])", "3"),
+            ("1 + len([1]) * 2", "3"),
+        ]);
+    }
+
+    #[test]
+    fn functions_and_closures() {
+        run_vm_tests(vec![
+            ("let one = 1; let two = one + one; one + two", "3"),
+            ("let f = fn() { 5 + 10; }; f();", "15"),
+            ("let f = fn() { return 99; 100; }; f();", "99"),
+            ("let noReturn = fn() { }; noReturn();", "null"),
+            (
+                "let g = 50; let f = fn(a, b) { let c = a + b; c + g }; f(1, 2) + f(3, 4)",
+                "110",
+            ),
+            (
+                "let newAdder = fn(a, b) { fn(c) { a + b + c } }; newAdder(1, 2)(8);",
+                "11",
+            ),
+            (
+                "let fibonacci = fn(x) {
+                    if (x == 0) { return 0; }
+                    if (x == 1) { return 1; }
+                    fibonacci(x - 1) + fibonacci(x - 2)
+                };
+                fibonacci(15);",
+                "610",
+            ),
+            (
+                "let wrapper = fn() {
+                    let countDown = fn(x) { if (x == 0) { return 0; } countDown(x - 1); };
+                    countDown(1);
+                };
+                wrapper();",
+                "0",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn repeated_constants() {
+        let mut input: String = (0..70000).map(|i| format!("{};", i % 7)).collect();
+        input.push_str("123456");
+
+        assert_eq!(run_vm(&input), Ok(String::from("123456")));
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let tests = vec![
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("10 / 0", "division by zero"),
+            ("10 / 0.0", "division by zero"),
+            ("1(2)", "not a function: INTEGER"),
+            (
+                "fn(x) { x }(1, 2)",
+                "wrong number of arguments: want=1, got=2",
+            ),
+            ("let f = fn() { f() }; f()", "stack overflow"),
+            (r#"{"a": 1}[fn(x) { x }]"#, "unusable as hash key: FUNCTION"),
+            ("len(1)", "argument to `len` not supported, got INTEGER"),
+            (
+                r#"len("one", "two")"#,
+                "wrong number of arguments: want=1, got=2",
+            ),
+            ("first(1)", "argument to `first` must be ARRAY, got INTEGER"),
+            (
+                "push(1, 1)",
+                "argument to `push` must be ARRAY, got INTEGER",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run_vm(input),
+                Err(String::from(expected)),
+                "input: {}",
+                input
+            );
+        }
+    }
+
+    // Both backends have to agree on every result and error message
+    #[test]
+    fn same_results_as_evaluator() {
+        let corpus = vec![
+            "5 + 5 + 5 + 5 - 10",
+            "3 * (3 * 3) + 10",
+            "!!5",
+            "(1 > 2) == true",
+            "1 == true",
+            "true < false",
+            "-true",
+            "5; true + false; 5",
+            "foobar",
+            "if (false) { foobar }",
+            "let a = fn() { b() }; let b = fn() { 1 }; a()",
+            "let a = fn() { b() }; a(); let b = fn() { 1 }",
+            "if (false) { let q = 1; }; q",
+            "let x = 1; let f = fn() { x }; let x = 2; f()",
+            "10 / 0",
+            "9223372036854775807 + 1",
+            "-9223372036854775807 - 2 * 3",
+            "let big = 4611686018427387904 * 2; [big, big / 2]",
+            "1.5 * 4 - 3 / 2",
+            "let half = fn(x) { x / 2.0 }; [half(3), half(-1.5), half(0)]",
+            "-7 / 2 + -7 / 2.0",
+            "1.5 / 0",
+            "1.5 == 1.5",
+            "{2.5: 1}",
+            "if (1) { 10 }",
+            "if (1 > 2) { 10 }",
+            "if (1 > 2) { 10 } else { 20 }",
+            "return 2 * 5; 9;",
+            "9; return 2 * 5; 9;",
+            "if (10 > 1) { if (10 > 1) { return 10; } return 1; }",
+            "if (10 > 1) { true + false; }",
+            "let a = 5; let b = a; let c = a + b + 5; c;",
+            "let identity = fn(x) { return x; }; identity(5);",
+            "let add = fn(x, y) { x + y }; add(5, 10)",
+            "let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));",
+            "fn(x) { x; }(5)",
+            "let x = 5; x(1)",
+            "let newAdder = fn(x) { fn(y) { x + y }; }; let addTwo = newAdder(2); addTwo(2);",
+            "let f = fn() { let a = 1; }; f()",
+            r#"let s = "ab"; s + "c" == "abc""#,
+            r#""a" - "b""#,
+            r#""a" < "b""#,
+            r#""1" + 1"#,
+            "[1, 2 * 2, fn(x) { x }(3)]",
+            "[1, 2 * 2, fn(x){x}][2](7)",
+            "let a = [1, [2, 3]]; a[1][0] + a[0]",
+            "[1, 2][2]",
+            "[1, 2][true]",
+            "5[0]",
+            "[1] + [2]",
+            "[1] == [1]",
+            r#"let h = {"one": 1, true: 2, 3: "three"}; [h["one"], h[true], h[3], h[4]]"#,
+            "{[1]: 2}",
+            "{1: 2} + {1: 2}",
+            "let a = [1, 2, 3, 4]; [len(a), first(a), last(a), rest(a), push(a, 5), a]",
+            "let reduce = fn(arr, initial, f) { let iter = fn(arr, result) { if (len(arr) == 0) { result } else { iter(rest(arr), f(result, first(arr))) } }; iter(arr, initial) }; reduce([1, 2, 3, 4, 5], 0, fn(a, b) { a + b })",
+            "let len = fn(x) { 0 }; len([1, 2])",
+            "let f = fn() { let first = 10; first }; f() + first([1])",
+            "len(1)",
+            "rest(true)",
+            "last([])",
+            "len",
+            r#"let people = [{"name": "Alice", "age": 24}, {"name": "Anna", "age": 28}]; people[1]["age"]"#,
+            r#"{"a": 1}[fn(x) { x }]"#,
+            "{fn() { 1 }: 2}",
+            "let add = fn(a, b) { a + b }; add",
+            "let add = fn(a, b) { a + b }; [add, fn(x) { x * 2 }]",
+            "let x = 5;",
+            "let x = 5; x; let y = x;",
+            "let f = fn() { len([1, 2]) }; let len = fn(x) { 42 }; f()",
+            "let f = fn() { len([1, 2]) }; let a = f(); let len = fn(x) { 42 }; [a, f()]",
+            "let g = fn() { let len = 3; len }; g() + len([1])",
+        ];
+
+        for input in corpus {
+            assert_eq!(run_vm(input), run_eval(input), "input: {}", input);
+        }
+    }
+}